@@ -0,0 +1,16 @@
+-- Users for the SQLite and PostgreSQL backends. Timestamps are RFC 3339
+-- text in UTC and roles a JSON array, so both databases share one schema.
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    username TEXT NOT NULL CHECK (length(username) BETWEEN 3 AND 32),
+    email TEXT NOT NULL,
+    password TEXT NOT NULL CHECK (password <> ''),
+    roles TEXT NOT NULL,
+    version BIGINT NOT NULL CHECK (version >= 1),
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    last_login_at TEXT,
+    last_login_ip TEXT
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email ON users (email);
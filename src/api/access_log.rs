@@ -0,0 +1,110 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use rocket::{
+    Data, Request, Response,
+    fairing::{Fairing, Info, Kind},
+};
+use tracing::{info, warn};
+
+use crate::{
+    api::request_id::{RequestId, RequestStart},
+    auth::jwt::authenticated_subject,
+    config::settings::AccessLogSettings,
+};
+
+/// Emits one `access_log` event per request once its response is ready.
+pub struct AccessLog {
+    settings: AccessLogSettings,
+}
+
+impl AccessLog {
+    pub fn new(settings: AccessLogSettings) -> Self {
+        Self { settings }
+    }
+
+    fn excluded(&self, path: &str) -> bool {
+        self.settings
+            .exclude_paths
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()))
+    }
+
+    /// Samples on the request id so a request is either logged everywhere
+    /// or nowhere, whichever instance serves it.
+    fn sampled(&self, request_id: &RequestId) -> bool {
+        let rate = self.settings.sample_rate;
+
+        if rate >= 1.0 {
+            return true;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        request_id.as_str().hash(&mut hasher);
+
+        (hasher.finish() % 10_000) as f64 / 10_000.0 < rate
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for AccessLog {
+    fn info(&self) -> Info {
+        Info {
+            name: "Access log",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestStart::record(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let path = request.uri().path();
+        let status = response.status();
+
+        if !self.settings.enabled || self.excluded(path.as_str()) {
+            return;
+        }
+
+        let request_id = RequestId::of(request);
+
+        if status.code < 500 && !self.sampled(request_id) {
+            return;
+        }
+
+        let latency_ms =
+            RequestStart::elapsed(request).map(|elapsed| elapsed.as_secs_f64() * 1000.0);
+
+        let route = request.route().map(|route| route.uri.to_string());
+        let size = response.body().preset_size();
+        let client_ip = request.client_ip().map(|ip| ip.to_string());
+        let sub = authenticated_subject(request);
+
+        macro_rules! access_event {
+            ($level:ident) => {
+                $level!(
+                    target: "access_log",
+                    request_id = %request_id.as_str(),
+                    method = %request.method(),
+                    path = %path,
+                    route = route.as_deref(),
+                    status = status.code,
+                    latency_ms,
+                    size,
+                    client_ip = client_ip.as_deref(),
+                    sub,
+                    "{} {} {}",
+                    request.method(),
+                    path,
+                    status.code,
+                )
+            };
+        }
+
+        if status.code >= 500 {
+            access_event!(warn);
+        } else {
+            access_event!(info);
+        }
+    }
+}
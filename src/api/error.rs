@@ -0,0 +1,228 @@
+use std::io::Cursor;
+
+use rocket::{
+    Catcher, Request, Response, catch, catchers,
+    http::{ContentType, Status},
+    response::{self, Responder},
+};
+use serde::Serialize;
+use tracing::{error, warn};
+use utoipa::ToSchema;
+use validator::ValidationErrors;
+
+use crate::{
+    api::request_id::RequestId,
+    auth::{jwt::JwtAuthenticationError, role_middleware::RoleAuthorizationError},
+    core::user::error::UserServiceError,
+};
+
+/// Error left behind by a failing request guard so the catcher that
+/// eventually answers can explain the failure.
+struct GuardFailure(Option<ApiError>);
+
+pub fn remember_guard_failure(request: &Request<'_>, error: ApiError) {
+    request.local_cache(|| GuardFailure(Some(error)));
+}
+
+/// RFC 7807 problem details body.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub instance: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+/// A single failing field in a `422` response.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Error returned by route handlers, rendered as `application/problem+json`.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    status: Status,
+    kind: Option<&'static str>,
+    detail: Option<String>,
+    errors: Vec<FieldError>,
+}
+
+impl ApiError {
+    pub fn new(status: Status, kind: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            kind: Some(kind),
+            detail: Some(detail.into()),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn from_status(status: Status) -> Self {
+        Self {
+            status,
+            kind: None,
+            detail: None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn problem(&self, request: &Request<'_>) -> ProblemDetails {
+        ProblemDetails {
+            kind: self
+                .kind
+                .map(|kind| format!("/problems/{kind}"))
+                .unwrap_or_else(|| "about:blank".to_string()),
+            title: self.status.reason_lossy().to_string(),
+            status: self.status.code,
+            detail: self.detail.clone(),
+            instance: request.uri().path().to_string(),
+            request_id: Some(RequestId::of(request).as_str().to_string()),
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let body = serde_json::to_string(&self.problem(request)).map_err(|e| {
+            error!("Failed to serialize problem details: {e}");
+            Status::InternalServerError
+        })?;
+
+        Response::build()
+            .status(self.status)
+            .header(ContentType::new("application", "problem+json"))
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        ApiError::from_status(status)
+    }
+}
+
+impl From<UserServiceError> for ApiError {
+    fn from(err: UserServiceError) -> Self {
+        match err {
+            UserServiceError::ValidationError(reason) => {
+                ApiError::new(Status::UnprocessableEntity, "validation-error", reason)
+            }
+            UserServiceError::PatchTestFailed(reason) => {
+                ApiError::new(Status::Conflict, "patch-test-failed", reason)
+            }
+            UserServiceError::InvalidFields(errors) => errors.into(),
+            UserServiceError::IncorrectPassword => {
+                ApiError::new(Status::Forbidden, "incorrect-password", err.to_string())
+            }
+            UserServiceError::UserNotFound => {
+                ApiError::new(Status::NotFound, "user-not-found", err.to_string())
+            }
+            UserServiceError::VersionMismatch => ApiError::new(
+                Status::PreconditionFailed,
+                "version-mismatch",
+                err.to_string(),
+            ),
+            UserServiceError::EmailTaken => {
+                ApiError::new(Status::Conflict, "email-taken", err.to_string())
+            }
+            UserServiceError::Unavailable(_) => {
+                warn!("User service unavailable: {}", err);
+                ApiError::new(
+                    Status::ServiceUnavailable,
+                    "database-unavailable",
+                    "The database is temporarily unavailable",
+                )
+            }
+            UserServiceError::PasswordHashError(_)
+            | UserServiceError::RepositoryError(_)
+            | UserServiceError::Unknown => {
+                error!("Internal user service error: {:?}", err);
+                ApiError::from_status(Status::InternalServerError)
+            }
+        }
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| FieldError {
+                    field: field.to_string(),
+                    code: error.code.to_string(),
+                    message: error.message.as_ref().map(|message| message.to_string()),
+                })
+            })
+            .collect();
+
+        fields.sort_by(|a, b| a.field.cmp(&b.field).then_with(|| a.code.cmp(&b.code)));
+
+        ApiError {
+            errors: fields,
+            ..ApiError::new(
+                Status::UnprocessableEntity,
+                "validation-error",
+                "One or more fields are invalid",
+            )
+        }
+    }
+}
+
+impl From<JwtAuthenticationError> for ApiError {
+    fn from(err: JwtAuthenticationError) -> Self {
+        let kind = match err {
+            JwtAuthenticationError::ExpiredToken => "expired-token",
+            JwtAuthenticationError::InvalidToken => "invalid-token",
+            JwtAuthenticationError::MissingToken => "missing-token",
+            JwtAuthenticationError::Unauthorized => "unauthorized",
+            JwtAuthenticationError::Unavailable => {
+                return ApiError::new(
+                    Status::ServiceUnavailable,
+                    "database-unavailable",
+                    err.to_string(),
+                );
+            }
+        };
+
+        ApiError::new(Status::Unauthorized, kind, err.to_string())
+    }
+}
+
+impl From<RoleAuthorizationError> for ApiError {
+    fn from(err: RoleAuthorizationError) -> Self {
+        ApiError::new(Status::Forbidden, "forbidden-role", err.to_string())
+    }
+}
+
+#[catch(default)]
+fn default_catcher(status: Status, request: &Request<'_>) -> ApiError {
+    request
+        .local_cache(|| GuardFailure(None))
+        .0
+        .clone()
+        .filter(|failure| failure.status == status)
+        .unwrap_or_else(|| ApiError::from_status(status))
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![default_catcher]
+}
@@ -4,9 +4,11 @@ use rocket::async_trait;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 
+use crate::api::error::{ApiError, remember_guard_failure};
+
 #[async_trait]
 pub trait Middleware: Sized {
-    type Error: Debug;
+    type Error: Debug + Clone + Into<ApiError>;
 
     async fn from_request(request: &Request<'_>) -> Result<Self, (Status, Self::Error)>;
 }
@@ -20,7 +22,10 @@ impl<'r, T: Middleware> FromRequest<'r> for MiddlewareGuard<T> {
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         match T::from_request(request).await {
             Ok(inner) => Outcome::Success(MiddlewareGuard(inner)),
-            Err((status, err)) => Outcome::Error((status, (status, err))),
+            Err((status, err)) => {
+                remember_guard_failure(request, err.clone().into());
+                Outcome::Error((status, (status, err)))
+            }
         }
     }
 }
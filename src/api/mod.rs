@@ -1,4 +1,9 @@
+pub mod access_log;
+pub mod error;
 pub mod middleware;
+pub mod openapi;
+pub mod request_id;
 pub mod requests;
 pub mod responses;
 pub mod routes;
+pub mod version;
@@ -0,0 +1,173 @@
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        OpenApiBuilder, Paths,
+        path::{Operation, PathItem},
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    },
+};
+
+use crate::{
+    api::requests::{SortOrder, Timestamp, UserSortField, auth_reqs::TokenResponse},
+    api::routes::{auth, export, import, user, v1, v2},
+    api::version::ApiVersion,
+    core::user::{export::ExportFormat, import::ImportFormat},
+};
+
+/// OpenAPI description of the routes every API version shares, relative to
+/// the version base. [`api_document`] adds each version's own routes and
+/// places them under its base.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "User API",
+        description = "User management and authentication. Every path is listed under \
+            each version base, `/api/v1` and `/api/v2`. The same routes are served under \
+            `/api`, where the version is negotiated from `Accept` \
+            (`application/json; version=2` or `application/vnd.user-api.v2+json`)."
+    ),
+    paths(
+        user::create_user,
+        user::delete_user,
+        user::reset_password,
+        auth::change_password,
+        auth::me,
+        auth::admin,
+        import::import_users,
+        import::import_status,
+        export::export_users,
+    ),
+    components(schemas(
+        ImportFormat,
+        ExportFormat,
+        SortOrder,
+        TokenResponse,
+        Timestamp,
+        UserSortField
+    )),
+    modifiers(&BearerSecurity),
+    tags(
+        (name = "users", description = "User management"),
+        (name = "auth", description = "Authentication and the current user"),
+        (name = "import", description = "Bulk user import"),
+        (name = "export", description = "Bulk user export"),
+    )
+)]
+pub struct ApiDoc;
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    v1::get_all_users,
+    v1::get_user,
+    v1::update_user,
+    v1::patch_user,
+    v1::login,
+))]
+struct V1Doc;
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    v2::get_all_users,
+    v2::get_user,
+    v2::update_user,
+    v2::patch_user,
+    v2::login,
+))]
+struct V2Doc;
+
+/// The routes only `version` serves, with the schemas they use.
+fn version_document(version: ApiVersion) -> utoipa::openapi::OpenApi {
+    match version {
+        ApiVersion::V1 => V1Doc::openapi(),
+        ApiVersion::V2 => V2Doc::openapi(),
+    }
+}
+
+/// Registers the JWT bearer scheme that operations refer to as `bearer`.
+struct BearerSecurity;
+
+impl Modify for BearerSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// The document served at `/api/openapi.json`. Operation ids are prefixed
+/// with the version, so they stay unique across versions.
+pub fn api_document() -> utoipa::openapi::OpenApi {
+    let shared = ApiDoc::openapi();
+    let mut document = shared.clone();
+    document.paths = Paths::new();
+
+    for version in ApiVersion::ALL {
+        let mut routes = version_document(version);
+        routes.paths.merge(shared.paths.clone());
+
+        let mut paths = routes.paths;
+        for operation in paths.paths.values_mut().flat_map(operations) {
+            operation.operation_id = operation
+                .operation_id
+                .take()
+                .map(|id| format!("{}_{id}", version.as_str()));
+        }
+
+        let routes = OpenApiBuilder::new()
+            .paths(paths)
+            .components(routes.components)
+            .build();
+        document = document.nest(version.base(), routes);
+    }
+
+    document
+}
+
+fn operations(item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+    [
+        &mut item.get,
+        &mut item.put,
+        &mut item.post,
+        &mut item.delete,
+        &mut item.options,
+        &mut item.head,
+        &mut item.patch,
+        &mut item.trace,
+    ]
+    .into_iter()
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    const CHECKED_IN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+    /// Fails when the routes drift from the checked-in `openapi.json`. Run
+    /// with `UPDATE_OPENAPI=1` to regenerate it.
+    #[test]
+    fn checked_in_document_is_current() {
+        let generated = api_document().to_pretty_json().unwrap() + "\n";
+
+        if env::var_os("UPDATE_OPENAPI").is_some() {
+            fs::write(CHECKED_IN, &generated).unwrap();
+        }
+
+        let checked_in = fs::read_to_string(CHECKED_IN).unwrap_or_default();
+        assert!(
+            checked_in == generated,
+            "openapi.json is out of date; rerun the test with UPDATE_OPENAPI=1 and commit it"
+        );
+    }
+}
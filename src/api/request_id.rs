@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use rocket::{
+    Data, Request, Response, Route,
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    request::{FromRequest, Outcome},
+    route::{self, Handler},
+};
+use tracing::{Instrument, Span, info_span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::telemetry::{self, TRACEPARENT_HEADER};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest client-supplied id that is accepted; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Correlation id of the current request, taken from `X-Request-Id` when the
+/// client sent a usable one and generated otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn of<'r>(request: &'r Request<'_>) -> &'r RequestId {
+        request.local_cache(|| {
+            request
+                .headers()
+                .get_one(REQUEST_ID_HEADER)
+                .filter(|id| is_valid(id))
+                .map(|id| RequestId(id.to_string()))
+                .unwrap_or_else(|| RequestId(uuid::Uuid::new_v4().to_string()))
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// When the request arrived, as first seen by any fairing that times it.
+/// The access log and the HTTP metrics share it, so both measure the same
+/// latency.
+pub struct RequestStart(Option<Instant>);
+
+impl RequestStart {
+    /// Records the arrival of `request`, unless an earlier fairing has.
+    pub fn record(request: &Request<'_>) {
+        request.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    /// Time since `request` arrived, if its arrival was recorded.
+    pub fn elapsed(request: &Request<'_>) -> Option<Duration> {
+        request
+            .local_cache(|| RequestStart(None))
+            .0
+            .map(|start| start.elapsed())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::of(request).clone())
+    }
+}
+
+/// Assigns every request its id and echoes it back in `X-Request-Id`, along
+/// with the `traceparent` of the request span when traces are exported.
+pub struct RequestIds;
+
+#[rocket::async_trait]
+impl Fairing for RequestIds {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ids",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestId::of(request);
+        request_span(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let id = RequestId::of(request);
+        response.set_header(Header::new(REQUEST_ID_HEADER, id.as_str().to_string()));
+
+        if let Some(traceparent) = telemetry::traceparent(request_span(request)) {
+            response.set_header(Header::new(TRACEPARENT_HEADER, traceparent));
+        }
+    }
+}
+
+/// Runs each route, guards included, inside a root `request` span carrying
+/// the request id, so everything logged while serving it can be correlated.
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Traced(route.handler));
+            route
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Traced {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        self.0
+            .handle(request, data)
+            .instrument(request_span(request).clone())
+            .await
+    }
+}
+
+struct RequestSpan(Span);
+
+/// Root span of the request, joined to the caller's trace when it sent a
+/// W3C `traceparent`.
+fn request_span<'r>(request: &'r Request<'_>) -> &'r Span {
+    &request
+        .local_cache(|| {
+            let span = info_span!(
+                "request",
+                request_id = %RequestId::of(request).as_str(),
+                method = %request.method(),
+                path = %request.uri().path(),
+            );
+            span.set_parent(telemetry::remote_context(request.headers()));
+            RequestSpan(span)
+        })
+        .0
+}
@@ -1,14 +1,41 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
 
-#[derive(Debug, Deserialize)]
+use crate::core::user::password_policy::{self, PasswordPolicy};
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct LoginRequest {
+    #[validate(email)]
     pub email: String,
+    #[validate(length(min = 1))]
     pub password: String,
 }
 
-#[derive(Debug, Serialize)]
+/// Self-service password change.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+#[validate(context = PasswordPolicy)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1))]
+    pub current_password: String,
+    #[validate(custom(function = "password_policy::validate", use_context))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct LoginResponse {
     pub token: String,
 }
+
+/// Login response from v2 on, shaped after an OAuth 2.0 token response.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    /// Seconds until the token expires.
+    pub expires_in: i64,
+}
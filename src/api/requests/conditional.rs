@@ -0,0 +1,146 @@
+use rocket::{
+    Request, async_trait,
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+
+use crate::{
+    api::{error::ApiError, responses::conditional::EntityTag},
+    core::user::dto::VersionPrecondition,
+};
+
+/// Parsed value of an `If-Match` or `If-None-Match` header. Tags this API
+/// did not issue are dropped, so they never match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityTagMatch {
+    Any,
+    Tags {
+        strong: Vec<EntityTag>,
+        weak: Vec<EntityTag>,
+    },
+}
+
+impl EntityTagMatch {
+    fn parse(header: &str) -> Self {
+        if header.trim() == "*" {
+            return EntityTagMatch::Any;
+        }
+
+        let mut strong = Vec::new();
+        let mut weak = Vec::new();
+        for tag in header.split(',').map(str::trim) {
+            match tag.strip_prefix("W/") {
+                Some(tag) => weak.extend(EntityTag::parse(tag)),
+                None => strong.extend(EntityTag::parse(tag)),
+            }
+        }
+
+        EntityTagMatch::Tags { strong, weak }
+    }
+
+    fn from_request(request: &Request<'_>, header: &str) -> Option<Self> {
+        let values: Vec<&str> = request.headers().get(header).collect();
+
+        if values.is_empty() {
+            None
+        } else {
+            Some(EntityTagMatch::parse(&values.join(",")))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct IfMatch(pub Option<EntityTagMatch>);
+
+impl IfMatch {
+    /// Turns the header into a write precondition, refusing to go without
+    /// one when `strict` is set. Only strong tags count (RFC 9110 §13.1.1),
+    /// and a write conflicts only on the version they carry.
+    pub fn precondition(self, strict: bool) -> Result<VersionPrecondition, ApiError> {
+        match self.0 {
+            None if strict => Err(ApiError::new(
+                Status::PreconditionRequired,
+                "precondition-required",
+                "This request requires an If-Match header",
+            )),
+            None | Some(EntityTagMatch::Any) => Ok(VersionPrecondition::Any),
+            Some(EntityTagMatch::Tags { strong, .. }) => Ok(VersionPrecondition::OneOf(
+                strong.iter().map(EntityTag::version).collect(),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfMatch(EntityTagMatch::from_request(request, "If-Match")))
+    }
+}
+
+#[derive(Debug)]
+pub struct IfNoneMatch(pub Option<EntityTagMatch>);
+
+impl IfNoneMatch {
+    /// Weak comparison (RFC 9110 §13.1.2): `W/` tags match too.
+    pub fn matches(&self, current: &EntityTag) -> bool {
+        match &self.0 {
+            None => false,
+            Some(EntityTagMatch::Any) => true,
+            Some(EntityTagMatch::Tags { strong, weak }) => {
+                strong.contains(current) || weak.contains(current)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(EntityTagMatch::from_request(
+            request,
+            "If-None-Match",
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn tag(version: u64, body: serde_json::Value) -> EntityTag {
+        EntityTag::new(version, &body)
+    }
+
+    #[test]
+    fn if_match_ignores_weak_tags() {
+        let current = tag(3, json!({ "username": "alice" }));
+
+        let weak = IfMatch(Some(EntityTagMatch::parse(&format!("W/{current}"))));
+        assert_eq!(
+            weak.precondition(false).unwrap(),
+            VersionPrecondition::OneOf(Vec::new())
+        );
+
+        let strong = IfMatch(Some(EntityTagMatch::parse(&current.to_string())));
+        assert_eq!(
+            strong.precondition(false).unwrap(),
+            VersionPrecondition::OneOf(vec![3])
+        );
+    }
+
+    #[test]
+    fn if_none_match_needs_the_same_representation() {
+        let cached = tag(3, json!({ "last_login_at": null }));
+        let header = IfNoneMatch(Some(EntityTagMatch::parse(&format!("W/{cached}"))));
+
+        assert!(header.matches(&cached));
+        assert!(!header.matches(&tag(3, json!({ "last_login_at": "2026-10-19T00:00:00Z" }))));
+    }
+}
@@ -1,11 +1,119 @@
-use rocket::FromForm;
+use chrono::{DateTime, Utc};
+use rocket::{
+    FromForm,
+    form::{self, FromFormField, ValueField},
+};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 pub mod auth_reqs;
+pub mod conditional;
 pub mod user_reqs;
+pub mod validated;
 
-#[derive(Debug, Serialize, Deserialize, FromForm)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromForm, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PageConfig {
+    #[validate(range(min = 1))]
     pub page: Option<u32>,
+    #[validate(range(min = 1, max = 100))]
     pub per_page: Option<u32>,
+    pub created_after: Option<Timestamp>,
+    pub created_before: Option<Timestamp>,
+    pub updated_after: Option<Timestamp>,
+    pub updated_before: Option<Timestamp>,
+    pub last_login_after: Option<Timestamp>,
+    /// Also matches users that never logged in.
+    pub last_login_before: Option<Timestamp>,
+    pub sort: Option<UserSortField>,
+    pub order: Option<SortOrder>,
+}
+
+impl PageConfig {
+    pub fn per_page(&self) -> u32 {
+        self.per_page.unwrap_or(10)
+    }
+
+    /// Offset of the first user on the page. Computed in `u64`, which no
+    /// `u32` page and page size can overflow.
+    pub fn start(&self) -> u64 {
+        u64::from(self.page.unwrap_or(1).saturating_sub(1)) * u64::from(self.per_page())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, FromFormField, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSortField {
+    #[field(value = "username")]
+    Username,
+    #[field(value = "email")]
+    Email,
+    #[field(value = "created_at")]
+    CreatedAt,
+    #[field(value = "updated_at")]
+    UpdatedAt,
+    #[field(value = "last_login_at")]
+    LastLoginAt,
+}
+
+impl UserSortField {
+    pub fn column(&self) -> &'static str {
+        match self {
+            UserSortField::Username => "username",
+            UserSortField::Email => "email",
+            UserSortField::CreatedAt => "created_at",
+            UserSortField::UpdatedAt => "updated_at",
+            UserSortField::LastLoginAt => "last_login_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, FromFormField, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    #[field(value = "asc")]
+    Asc,
+    #[field(value = "desc")]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// RFC 3339 timestamp accepted as a query parameter.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(transparent)]
+#[schema(value_type = String, format = DateTime)]
+pub struct Timestamp(pub DateTime<Utc>);
+
+impl<'v> FromFormField<'v> for Timestamp {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        DateTime::parse_from_rfc3339(field.value)
+            .map(|dt| Timestamp(dt.with_timezone(&Utc)))
+            .map_err(|e| form::Error::validation(e.to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn last_page_offset_does_not_overflow() {
+        let spec: PageConfig =
+            serde_json::from_value(json!({ "page": u32::MAX, "per_page": 100 })).unwrap();
+
+        assert!(spec.validate().is_ok());
+        assert_eq!(spec.start(), u64::from(u32::MAX - 1) * 100);
+    }
 }
@@ -1,23 +1,101 @@
+use rocket::{
+    Request,
+    data::{Data, FromData, Limits, Outcome},
+    http::{ContentType, Status},
+};
 use serde::Deserialize;
+use utoipa::ToSchema;
+use validator::Validate;
 
-use crate::auth::roles::Role;
+use crate::{
+    api::error::{ApiError, remember_guard_failure},
+    auth::roles::Role,
+    core::user::{
+        dto::UserPatch,
+        password_policy::{self, PasswordPolicy},
+    },
+};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[validate(context = PasswordPolicy)]
 pub struct CreateUserRequest {
+    #[validate(length(min = 3, max = 32))]
     pub username: String,
+    #[validate(email)]
     pub email: String,
+    #[validate(custom(function = "password_policy::validate", use_context))]
     pub password: String,
     pub roles: Vec<Role>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct UpdateUserRequest {
-    pub username: Option<String>,
-    pub email: Option<String>,
-    pub password: Option<String>,
+/// Administrative password reset.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[validate(context = PasswordPolicy)]
+pub struct ResetPasswordRequest {
+    #[validate(custom(function = "password_policy::validate", use_context))]
+    pub new_password: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct DeleteUserRequest {
-    pub id: String,
+#[derive(Debug, thiserror::Error)]
+pub enum UserPatchError {
+    #[error("Unsupported patch media type")]
+    UnsupportedMediaType,
+
+    #[error("Patch body exceeds the size limit")]
+    TooLarge,
+
+    #[error("Failed to read patch body: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed patch document: {0}")]
+    Malformed(#[from] serde_json::Error),
+}
+
+async fn read_patch(
+    req: &Request<'_>,
+    data: Data<'_>,
+) -> Result<UserPatch, (Status, UserPatchError)> {
+    let merge_patch = ContentType::new("application", "merge-patch+json");
+    let json_patch = ContentType::new("application", "json-patch+json");
+
+    let content_type = req.content_type();
+    let is_merge = content_type.is_some_and(|ct| ct.media_type() == merge_patch.media_type());
+    let is_json = content_type.is_some_and(|ct| ct.media_type() == json_patch.media_type());
+
+    if !is_merge && !is_json {
+        return Err((
+            Status::UnsupportedMediaType,
+            UserPatchError::UnsupportedMediaType,
+        ));
+    }
+
+    let limit = req.limits().get("json").unwrap_or(Limits::JSON);
+    let body = match data.open(limit).into_string().await {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        Ok(_) => return Err((Status::PayloadTooLarge, UserPatchError::TooLarge)),
+        Err(e) => return Err((Status::BadRequest, e.into())),
+    };
+
+    let patch = if is_merge {
+        serde_json::from_str(&body).map(UserPatch::Merge)
+    } else {
+        serde_json::from_str(&body).map(UserPatch::Json)
+    };
+
+    patch.map_err(|e| (Status::UnprocessableEntity, e.into()))
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for UserPatch {
+    type Error = UserPatchError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r, Self> {
+        match read_patch(req, data).await {
+            Ok(patch) => Outcome::Success(patch),
+            Err((status, e)) => {
+                remember_guard_failure(req, ApiError::new(status, "invalid-patch", e.to_string()));
+                Outcome::Error((status, e))
+            }
+        }
+    }
 }
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use rocket::{
+    Orbit, Request, Rocket,
+    data::{Data, FromData, Outcome},
+    http::Status,
+    serde::json::{self, Json},
+};
+use serde::Deserialize;
+use validator::{ValidateArgs, ValidationErrors};
+
+use crate::{
+    api::error::{ApiError, remember_guard_failure},
+    core::user::{password_policy::PasswordPolicy, service::UserService},
+};
+
+/// Data guard that deserializes a body and rejects it with `422` unless
+/// every field passes its `#[validate]` rules.
+#[derive(Debug)]
+pub struct Validated<T>(pub T);
+
+impl<T> Validated<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> std::ops::Deref for Validated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[derive(Debug)]
+pub enum ValidatedError<'r> {
+    Parse(json::Error<'r>),
+    Invalid(ValidationErrors),
+    MissingContext,
+}
+
+/// Context a `#[validate(context = ...)]` type needs, taken from managed state.
+pub trait ValidationContext<'r>: Sized {
+    fn from_rocket(rocket: &'r Rocket<Orbit>) -> Option<Self>;
+}
+
+impl<'r> ValidationContext<'r> for () {
+    fn from_rocket(_: &'r Rocket<Orbit>) -> Option<Self> {
+        Some(())
+    }
+}
+
+impl<'r> ValidationContext<'r> for &'r PasswordPolicy {
+    fn from_rocket(rocket: &'r Rocket<Orbit>) -> Option<Self> {
+        rocket
+            .state::<Arc<UserService>>()
+            .map(|service| service.password_policy())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T> FromData<'r> for Validated<Json<T>>
+where
+    T: Deserialize<'r> + ValidateArgs<'r>,
+    T::Args: ValidationContext<'r>,
+{
+    type Error = ValidatedError<'r>;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r, Self> {
+        let body = match Json::<T>::from_data(req, data).await {
+            Outcome::Success(body) => body,
+            Outcome::Error((status, e)) => {
+                remember_guard_failure(req, ApiError::new(status, "malformed-body", e.to_string()));
+                return Outcome::Error((status, ValidatedError::Parse(e)));
+            }
+            Outcome::Forward(forward) => return Outcome::Forward(forward),
+        };
+
+        let Some(context) = T::Args::from_rocket(req.rocket()) else {
+            return Outcome::Error((Status::InternalServerError, ValidatedError::MissingContext));
+        };
+
+        match body.validate_with_args(context) {
+            Ok(()) => Outcome::Success(Validated(body)),
+            Err(errors) => {
+                remember_guard_failure(req, ApiError::from(errors.clone()));
+                Outcome::Error((Status::UnprocessableEntity, ValidatedError::Invalid(errors)))
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+
+use rocket::{
+    Request, Response,
+    http::{Header, Status},
+    response::{self, Responder},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Strong entity tag of a representation: the resource version, which write
+/// preconditions check, and a digest of the body, which also changes when a
+/// login or another API version alters the body at the same version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    version: u64,
+    digest: String,
+}
+
+impl EntityTag {
+    pub fn new<T: Serialize>(version: u64, representation: &T) -> Self {
+        let body = serde_json::to_vec(representation).unwrap_or_default();
+        let digest = Sha256::digest(body);
+
+        Self {
+            version,
+            digest: digest[..8].iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
+
+    /// Reads a quoted `"<version>-<digest>"` tag as written by `Display`.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let (version, digest) = tag.strip_prefix('"')?.strip_suffix('"')?.split_once('-')?;
+
+        Some(Self {
+            version: version.parse().ok()?,
+            digest: digest.to_string(),
+        })
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}-{}\"", self.version, self.digest)
+    }
+}
+
+fn etag(tag: &EntityTag) -> Header<'static> {
+    Header::new("ETag", tag.to_string())
+}
+
+/// Wraps a response with the `ETag` of the representation it carries.
+pub struct Versioned<R>(pub R, pub EntityTag);
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Versioned<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        Response::build_from(self.0.respond_to(request)?)
+            .header(etag(&self.1))
+            .ok()
+    }
+}
+
+/// `304 Not Modified` for a representation the client already has.
+pub struct NotModified(pub EntityTag);
+
+impl<'r> Responder<'r, 'static> for NotModified {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::NotModified)
+            .header(etag(&self.0))
+            .ok()
+    }
+}
+
+/// Answer to a `GET` that may be served from the client's cached copy.
+pub enum Conditional<R> {
+    Modified(Versioned<R>),
+    NotModified(NotModified),
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Conditional<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        match self {
+            Conditional::Modified(response) => response.respond_to(request),
+            Conditional::NotModified(response) => response.respond_to(request),
+        }
+    }
+}
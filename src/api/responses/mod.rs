@@ -1 +1,2 @@
+pub mod conditional;
 pub mod user;
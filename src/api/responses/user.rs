@@ -1,16 +1,79 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use rocket::{Responder, http::Header, serde::json::Json};
 use serde::Serialize;
+use utoipa::ToSchema;
 
-use crate::auth::roles::Role;
+use crate::{
+    api::version::ApiVersion,
+    auth::roles::Role,
+    core::user::{
+        import::{ImportJob, ImportReport},
+        model::User,
+    },
+};
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CreateUserResponse {
     pub id: String,
     pub username: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UserDTO {
+    /// Only present from v2 on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub username: String,
     pub email: String,
     pub roles: Vec<Role>,
+    pub version: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>)]
+    pub last_login_ip: Option<IpAddr>,
+}
+
+impl UserDTO {
+    /// Renders `user` the way `version` represents it.
+    pub fn versioned(user: User, version: ApiVersion) -> Self {
+        let id = (version >= ApiVersion::V2).then(|| user.id.clone());
+
+        UserDTO {
+            id,
+            ..UserDTO::from(user)
+        }
+    }
+}
+
+impl From<User> for UserDTO {
+    fn from(user: User) -> Self {
+        Self {
+            id: None,
+            username: user.username,
+            email: user.email,
+            roles: user.roles,
+            version: user.version,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            last_login_at: user.last_login_at,
+            last_login_ip: user.last_login_ip,
+        }
+    }
+}
+
+#[derive(Responder)]
+pub enum ImportResponse {
+    Report(Json<ImportReport>),
+    #[response(status = 202)]
+    Accepted(Json<ImportJob>, Header<'static>),
+}
+
+/// Response served as a file download.
+#[derive(Responder)]
+pub struct Attachment<R> {
+    pub inner: R,
+    pub disposition: Header<'static>,
 }
@@ -1,45 +1,112 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
-use rocket::{Route, State, get, http::Status, post, serde::json::Json};
+use rocket::{Route, State, get, http::Status, put, serde::json::Json};
 use tracing::{info, instrument};
 
 use crate::{
     api::{
+        error::{ApiError, ProblemDetails},
         middleware::MiddlewareGuard,
-        requests::auth_reqs::{LoginRequest, LoginResponse},
+        requests::{
+            auth_reqs::{ChangePasswordRequest, LoginRequest},
+            validated::Validated,
+        },
     },
     auth::{
         jwt::JwtAuthentication, role_middleware::RoleAuthorization, roles::Admin,
         service::AuthService,
     },
+    core::user::{error::UserServiceError, service::UserService},
 };
 
 pub fn routes() -> Vec<Route> {
-    rocket::routes![login, me, admin]
+    rocket::routes![change_password, me, admin]
 }
 
+/// Checks `credentials` and issues a token; [`super::versioned`] wraps it in
+/// each version's own login response.
 #[instrument(name = "login_request", skip(auth_service, credentials))]
-#[post("/auth", data = "<credentials>")]
-async fn login(
-    credentials: Json<LoginRequest>,
+pub(super) async fn login(
+    credentials: Validated<Json<LoginRequest>>,
+    client_ip: Option<IpAddr>,
     auth_service: &State<Arc<AuthService>>,
-) -> Result<Json<LoginResponse>, Status> {
+) -> Result<String, ApiError> {
     let token = auth_service
-        .login(credentials.email.clone(), credentials.password.clone())
+        .login(
+            credentials.email.clone(),
+            credentials.password.clone(),
+            client_ip,
+        )
         .await
-        .map_err(|_| Status::Unauthorized)?;
+        .map_err(|e| match e {
+            UserServiceError::Unavailable(_) => ApiError::from(e),
+            _ => ApiError::new(
+                Status::Unauthorized,
+                "invalid-credentials",
+                "Invalid email or password",
+            ),
+        })?;
 
-    info!("Login received with: {} email", credentials.email);
-    Ok(Json(LoginResponse { token }))
+    Ok(token)
+}
+
+#[instrument(name = "change_password", skip(auth, request, user_service), fields(user_id = %auth.0.0.sub))]
+#[utoipa::path(
+    tag = "auth",
+    request_body = ChangePasswordRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Current password is incorrect", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Password policy violation", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[put("/auth/password", data = "<request>")]
+async fn change_password(
+    auth: MiddlewareGuard<JwtAuthentication>,
+    request: Validated<Json<ChangePasswordRequest>>,
+    user_service: &State<Arc<UserService>>,
+) -> Result<Status, ApiError> {
+    let request = request.into_inner().into_inner();
+
+    user_service
+        .change_password(
+            auth.0.0.sub.clone(),
+            request.current_password,
+            request.new_password,
+        )
+        .await?;
+
+    info!("Password changed");
+    Ok(Status::NoContent)
 }
 
 #[instrument(name = "protected_me", skip(auth))]
+#[utoipa::path(
+    tag = "auth",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Claims of the authenticated user", body = String),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 #[get("/me")]
 async fn me(auth: MiddlewareGuard<JwtAuthentication>) -> String {
     format!("Usuário autenticado: {:?}", auth.0)
 }
 
 #[instrument(name = "admin_test", skip(_jwt, _auth))]
+#[utoipa::path(
+    tag = "auth",
+    security(("bearer" = [])),
+    extensions(("x-required-roles" = json!(["Admin"]))),
+    responses(
+        (status = 200, description = "Caller is an administrator", body = String),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is not an administrator", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 #[get("/admin")]
 async fn admin(
     _jwt: MiddlewareGuard<JwtAuthentication>,
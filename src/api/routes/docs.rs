@@ -0,0 +1,26 @@
+use rocket::{Route, get, serde::json::Json};
+use utoipa::openapi::OpenApi;
+use utoipa_rapidoc::RapiDoc;
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+use crate::api::openapi::api_document;
+
+const SPEC_URL: &str = "/api/openapi.json";
+
+/// Documentation routes. They carry their full paths and are mounted at `/`,
+/// because Swagger UI resolves its bundled assets against the request path.
+pub fn routes() -> Vec<Route> {
+    let mut routes = rocket::routes![openapi_json];
+    routes.extend(Vec::<Route>::from(
+        SwaggerUi::new("/api/docs/<_..>").config(Config::from(SPEC_URL)),
+    ));
+    routes.extend(Vec::<Route>::from(
+        RapiDoc::new(SPEC_URL).path("/api/rapidoc"),
+    ));
+    routes
+}
+
+#[get("/api/openapi.json")]
+fn openapi_json() -> Json<OpenApi> {
+    Json(api_document())
+}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use rocket::{self, Route, State, get, http::Status, serde::json::Json};
+use serde::Serialize;
+
+use crate::infra::health::{HealthCheck, Readiness, ReadinessStatus};
+
+pub fn routes() -> Vec<Route> {
+    rocket::routes![live, ready]
+}
+
+#[derive(Serialize)]
+pub struct Liveness {
+    status: &'static str,
+}
+
+/// The process is up and serving requests; dependencies are not consulted.
+#[get("/health/live")]
+fn live() -> Json<Liveness> {
+    Json(Liveness { status: "up" })
+}
+
+/// Per-component readiness; `503` unless every component is up and the
+/// service is not shutting down.
+#[get("/health/ready")]
+async fn ready(health: &State<Arc<HealthCheck>>) -> (Status, Json<Readiness>) {
+    let readiness = health.readiness().await;
+    let status = match readiness.status {
+        ReadinessStatus::Ready => Status::Ok,
+        ReadinessStatus::NotReady | ReadinessStatus::ShuttingDown => Status::ServiceUnavailable,
+    };
+
+    (status, Json(readiness))
+}
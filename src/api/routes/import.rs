@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use rocket::{
+    Route, State,
+    data::{Data, Limits, ToByteUnit},
+    get,
+    http::{ContentType, Header, Status},
+    post,
+    serde::json::Json,
+};
+use tracing::{Instrument, error, info, instrument};
+
+use crate::{
+    api::{
+        error::{ApiError, ProblemDetails},
+        middleware::MiddlewareGuard,
+        responses::user::ImportResponse,
+    },
+    auth::{jwt::JwtAuthentication, role_middleware::RoleAuthorization, roles::Admin},
+    core::user::{
+        import::{self, ImportFormat, ImportJob, ImportJobs, ImportReport},
+        service::UserService,
+    },
+};
+
+const DEFAULT_CHUNK_SIZE: usize = 100;
+
+pub fn routes() -> Vec<Route> {
+    rocket::routes![import_users, import_status]
+}
+
+fn detect_format(content_type: Option<&ContentType>) -> Option<ImportFormat> {
+    let content_type = content_type?;
+
+    match (content_type.top().as_str(), content_type.sub().as_str()) {
+        ("text", "csv") => Some(ImportFormat::Csv),
+        ("application", "x-ndjson" | "jsonl" | "jsonlines") => Some(ImportFormat::JsonLines),
+        _ => None,
+    }
+}
+
+#[instrument(
+    name = "import_users",
+    skip(_jwt, _auth, body, content_type, limits, route, user_service, jobs)
+)]
+#[utoipa::path(
+    tag = "import",
+    params(("format" = Option<ImportFormat>, Query, description = "Overrides the format detected from Content-Type")),
+    request_body(content(("text/csv"), ("application/x-ndjson"))),
+    security(("bearer" = [])),
+    extensions(("x-required-roles" = json!(["Admin"]))),
+    responses(
+        (status = 200, description = "Dry-run report", body = ImportReport),
+        (status = 202, description = "Import job accepted", body = ImportJob, headers(("Location" = String))),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is not an administrator", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 413, description = "Import body too large", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 415, description = "Unsupported import format", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[post("/users/import?<format>&<dry_run>&<chunk_size>", data = "<body>")]
+#[allow(clippy::too_many_arguments)]
+async fn import_users(
+    _jwt: MiddlewareGuard<JwtAuthentication>,
+    _auth: MiddlewareGuard<RoleAuthorization<Admin>>,
+    format: Option<ImportFormat>,
+    dry_run: Option<bool>,
+    chunk_size: Option<usize>,
+    content_type: Option<&ContentType>,
+    body: Data<'_>,
+    limits: &Limits,
+    route: &Route,
+    user_service: &State<Arc<UserService>>,
+    jobs: &State<Arc<ImportJobs>>,
+) -> Result<ImportResponse, ApiError> {
+    let format = format
+        .or_else(|| detect_format(content_type))
+        .ok_or_else(|| {
+            ApiError::new(
+                Status::UnsupportedMediaType,
+                "unsupported-import-format",
+                "Send text/csv or application/x-ndjson, or pass ?format=csv|jsonl",
+            )
+        })?;
+
+    let limit = limits.get("import").unwrap_or(8.mebibytes());
+    let body = body.open(limit).into_string().await.map_err(|e| {
+        error!("Failed to read import body: {:?}", e);
+        Status::BadRequest
+    })?;
+
+    if !body.is_complete() {
+        return Err(ApiError::new(
+            Status::PayloadTooLarge,
+            "import-too-large",
+            format!("Import body exceeds {limit}"),
+        ));
+    }
+
+    let (rows, errors) = import::parse(format, &body);
+    let total = rows.len() + errors.len();
+
+    if dry_run.unwrap_or(false) {
+        let (valid, invalid) = user_service.check_import(rows).await;
+        let mut errors = errors;
+        errors.extend(invalid);
+        errors.sort_by_key(|e| e.line);
+
+        return Ok(ImportResponse::Report(Json(ImportReport {
+            total,
+            valid: valid.len(),
+            imported: 0,
+            errors,
+        })));
+    }
+
+    let job = jobs.create(total, errors);
+    info!(job_id = %job.id, total, "Import job accepted");
+
+    rocket::tokio::spawn(
+        import::run_import(
+            Arc::clone(user_service),
+            Arc::clone(jobs),
+            job.id.clone(),
+            rows,
+            chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE),
+        )
+        .in_current_span(),
+    );
+
+    let status_uri = format!("{}/users/import/{}", route.uri.base(), job.id);
+    let location = Header::new("Location", status_uri);
+    Ok(ImportResponse::Accepted(Json(job), location))
+}
+
+#[instrument(name = "import_status", skip(_jwt, _auth, jobs))]
+#[utoipa::path(
+    tag = "import",
+    security(("bearer" = [])),
+    extensions(("x-required-roles" = json!(["Admin"]))),
+    responses(
+        (status = 200, description = "Import job status", body = ImportJob),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is not an administrator", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Import job not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[get("/users/import/<job_id>")]
+async fn import_status(
+    job_id: String,
+    _jwt: MiddlewareGuard<JwtAuthentication>,
+    _auth: MiddlewareGuard<RoleAuthorization<Admin>>,
+    jobs: &State<Arc<ImportJobs>>,
+) -> Result<Json<ImportJob>, ApiError> {
+    jobs.get(&job_id).map(Json).ok_or_else(|| {
+        ApiError::new(
+            Status::NotFound,
+            "import-job-not-found",
+            format!("No import job with id {job_id}"),
+        )
+    })
+}
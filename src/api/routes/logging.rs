@@ -0,0 +1,55 @@
+use rocket::{self, Route, get, http::Status, put};
+use tracing::{info, instrument};
+
+use crate::{
+    api::{error::ApiError, middleware::MiddlewareGuard},
+    auth::{jwt::JwtAuthentication, role_middleware::RoleAuthorization, roles::Admin},
+    telemetry::{LogFilter, log_filter},
+};
+
+pub fn routes() -> Vec<Route> {
+    rocket::routes![get_log_filter, set_log_filter]
+}
+
+fn installed_filter() -> Result<&'static LogFilter, ApiError> {
+    log_filter().ok_or_else(|| {
+        ApiError::new(
+            Status::ServiceUnavailable,
+            "log-filter-unavailable",
+            "Logging has not been initialised",
+        )
+    })
+}
+
+/// Active log filter, in `RUST_LOG` syntax.
+#[instrument(name = "get_log_filter", skip(_jwt, _auth))]
+#[get("/log-filter")]
+async fn get_log_filter(
+    _jwt: MiddlewareGuard<JwtAuthentication>,
+    _auth: MiddlewareGuard<RoleAuthorization<Admin>>,
+) -> Result<String, ApiError> {
+    Ok(installed_filter()?.current())
+}
+
+/// Replaces the log filter with the directives in the request body.
+#[instrument(name = "set_log_filter", skip(_jwt, _auth))]
+#[put("/log-filter", data = "<directives>")]
+async fn set_log_filter(
+    _jwt: MiddlewareGuard<JwtAuthentication>,
+    _auth: MiddlewareGuard<RoleAuthorization<Admin>>,
+    directives: String,
+) -> Result<String, ApiError> {
+    let filter = installed_filter()?;
+
+    filter.set(directives.trim()).map_err(|e| {
+        ApiError::new(
+            Status::UnprocessableEntity,
+            "invalid-log-filter",
+            e.to_string(),
+        )
+    })?;
+
+    let current = filter.current();
+    info!(filter = %current, "Log filter reloaded");
+    Ok(current)
+}
@@ -1,11 +1,43 @@
 pub mod auth;
+pub mod docs;
+pub mod export;
+pub mod health;
+pub mod import;
+pub mod logging;
 pub mod user;
+pub mod v1;
+pub mod v2;
+mod versioned;
 
 use rocket::Route;
 
-pub fn get_routes() -> Vec<Route> {
+use crate::api::version::ApiVersion;
+
+/// Routes every API version serves the same way.
+fn shared_routes() -> Vec<Route> {
     let mut routes = Vec::new();
     routes.extend(user::routes());
     routes.extend(auth::routes());
+    routes.extend(import::routes());
+    routes.extend(export::routes());
+    routes
+}
+
+/// The routes mounted at `version`'s base.
+pub fn versioned_routes(version: ApiVersion) -> Vec<Route> {
+    let mut routes = shared_routes();
+    routes.extend(match version {
+        ApiVersion::V1 => v1::routes(),
+        ApiVersion::V2 => v2::routes(),
+    });
+    routes
+}
+
+/// The routes mounted at `/api`: every version's, each admitting only the
+/// requests that negotiated it.
+pub fn negotiated_routes() -> Vec<Route> {
+    let mut routes = shared_routes();
+    routes.extend(v1::routes());
+    routes.extend(v2::routes());
     routes
 }
@@ -1,30 +1,58 @@
 use std::sync::Arc;
 
-use rocket::{self, Route, State, delete, get, http::Status, post, put, serde::json::Json};
+use rocket::{self, Route, State, delete, http::Status, post, put, serde::json::Json};
 use tracing::{debug, error, info, instrument};
+use validator::Validate;
 
 use crate::{
     api::{
-        requests::{user_reqs::CreateUserRequest, PageConfig},
-        responses::user::{CreateUserResponse, UserDTO},
+        error::{ApiError, ProblemDetails},
+        middleware::MiddlewareGuard,
+        requests::{
+            PageConfig,
+            conditional::{IfMatch, IfNoneMatch},
+            user_reqs::{CreateUserRequest, ResetPasswordRequest},
+            validated::Validated,
+        },
+        responses::{
+            conditional::{Conditional, EntityTag, NotModified, Versioned},
+            user::{CreateUserResponse, UserDTO},
+        },
+        version::Representation,
+    },
+    auth::{jwt::JwtAuthentication, role_middleware::RoleAuthorization, roles::Admin},
+    config::settings::Settings,
+    core::user::{
+        dto::{UpdateUser, UserPatch},
+        error::UserServiceError,
+        model::User,
+        service::UserService,
     },
-    core::user::{dto::UpdateUser, model::User, service::UserService},
 };
 
 pub fn routes() -> Vec<Route> {
-    rocket::routes![create_user, get_all_users, delete_user, update_user]
+    rocket::routes![create_user, delete_user, reset_password]
 }
 
 #[instrument(
     name = "create_user_request",
     skip(new_user, user_service),
-    fields(user_email = %new_user.email, user_username = %new_user.username)
+    fields(user_username = %new_user.username)
+)]
+#[utoipa::path(
+    tag = "users",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 200, description = "User created", body = CreateUserResponse),
+        (status = 409, description = "Email is already taken", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid fields or password policy violation", body = ProblemDetails, content_type = "application/problem+json"),
+    )
 )]
 #[post("/users", data = "<new_user>")]
 pub async fn create_user(
-    new_user: Json<CreateUserRequest>,
+    new_user: Validated<Json<CreateUserRequest>>,
     user_service: &State<Arc<UserService>>,
-) -> Result<Json<CreateUserResponse>, Status> {
+) -> Result<Json<CreateUserResponse>, ApiError> {
     info!("Initializing new user creation");
 
     user_service
@@ -46,53 +74,154 @@ pub async fn create_user(
         .map_err(|e| {
             error!("Failed to create user: {:?}", e);
 
-            Status::InternalServerError
+            e.into()
         })
 }
 
+/// The version-specific routes in [`super::versioned`] render users through
+/// the handlers below, as the version `V` represents them.
 #[instrument(
-    name = "get_all_users", 
-    skip(user_service, spec), 
+    name = "get_all_users",
+    skip(user_service, spec),
     fields(page = %spec.page.unwrap_or(1), per_page = %spec.per_page.unwrap_or(10))
 )]
-#[get("/users?<spec..>")]
-async fn get_all_users(
+pub(super) async fn get_all_users<V: Representation>(
     spec: PageConfig,
     user_service: &State<Arc<UserService>>,
-) -> Result<Json<Vec<UserDTO>>, Status> {
+) -> Result<Json<Vec<UserDTO>>, ApiError> {
+    spec.validate()?;
+
     let users = user_service
         .list_users(spec)
         .await
-        .map_err(|_| {
+        .map_err(|e| {
             error!("Error to get users.");
-            Status::NotFound})?.into_iter().map(|e| UserDTO {
-                email: e.email,
-                username: e.username,
-                roles: e.roles,
-
-            }).collect();
+            ApiError::from(e)
+        })?
+        .into_iter()
+        .map(V::user)
+        .collect();
 
     debug!("Successful to get users.");
     Ok(Json(users))
 }
 
-#[instrument(name="delete_user", skip(user_service), fields(id = id))]
+#[instrument(name = "get_user", skip(user_service, if_none_match), fields(id = id))]
+pub(super) async fn get_user<V: Representation>(
+    id: String,
+    if_none_match: IfNoneMatch,
+    user_service: &State<Arc<UserService>>,
+) -> Result<Conditional<Json<UserDTO>>, ApiError> {
+    let user = user_service
+        .find_by_id(id)
+        .await?
+        .ok_or(UserServiceError::UserNotFound)?;
+
+    let response = tagged::<V>(user);
+    if if_none_match.matches(&response.1) {
+        return Ok(Conditional::NotModified(NotModified(response.1)));
+    }
+
+    Ok(Conditional::Modified(response))
+}
+
+#[instrument(name="delete_user", skip(user_service, if_match, settings), fields(id = id))]
+#[utoipa::path(
+    tag = "users",
+    params(("If-Match" = Option<String>, Header, description = "ETag the user must still have")),
+    responses(
+        (status = 204, description = "User deleted"),
+        (status = 404, description = "User not found", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 412, description = "User was modified", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 428, description = "If-Match is required", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 #[delete("/users?<id>")]
-async fn delete_user(id: String, user_service: &State<Arc<UserService>>) -> Status {
-    user_service.delete_user(id).await.map(|_| Status::NoContent).unwrap_or(Status::NotFound)
+async fn delete_user(
+    id: String,
+    if_match: IfMatch,
+    user_service: &State<Arc<UserService>>,
+    settings: &State<Settings>,
+) -> Result<Status, ApiError> {
+    let precondition = if_match.precondition(settings.server.strict_preconditions)?;
+
+    user_service.delete_user(id, precondition).await?;
+
+    Ok(Status::NoContent)
+}
+
+#[instrument(name = "update_user", skip(user_data, if_match, user_service, settings), fields(id = id))]
+pub(super) async fn update_user<V: Representation>(
+    id: String,
+    user_data: Validated<Json<UpdateUser>>,
+    if_match: IfMatch,
+    user_service: &State<Arc<UserService>>,
+    settings: &State<Settings>,
+) -> Result<Versioned<Json<UserDTO>>, ApiError> {
+    let precondition = if_match.precondition(settings.server.strict_preconditions)?;
+
+    let user = user_service
+        .update_user(id, user_data.into_inner().into_inner(), precondition)
+        .await?;
+
+    Ok(tagged::<V>(user))
 }
 
-#[instrument(name="update_user", skip(user_service), fields(username = user_data.username, email = user_data.email))]
-#[put("/users?<id>", data = "<user_data>")]
-async fn update_user(
+#[instrument(name = "patch_user", skip(user_service, patch, if_match, settings), fields(id = id))]
+pub(super) async fn patch_user<V: Representation>(
     id: String,
-    user_data: Json<UpdateUser>,
+    patch: UserPatch,
+    if_match: IfMatch,
     user_service: &State<Arc<UserService>>,
-) -> Result<Json<User>, Status> {
+    settings: &State<Settings>,
+) -> Result<Versioned<Json<UserDTO>>, ApiError> {
+    let precondition = if_match.precondition(settings.server.strict_preconditions)?;
+
     let user = user_service
-        .update_user(id, user_data.into_inner())
+        .patch_user(id, patch, precondition)
         .await
-        .map_err(|_| Status::NotFound)?;
+        .inspect_err(|e| debug!("Rejected user patch: {e}"))?;
+
+    Ok(tagged::<V>(user))
+}
+
+#[instrument(name = "reset_password", skip(_jwt, _auth, request, user_service), fields(id = id))]
+#[utoipa::path(
+    tag = "users",
+    request_body = ResetPasswordRequest,
+    security(("bearer" = [])),
+    extensions(("x-required-roles" = json!(["Admin"]))),
+    responses(
+        (status = 204, description = "Password reset"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Caller is not an administrator", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "User not found", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Password policy violation", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[put("/users/<id>/password", data = "<request>")]
+async fn reset_password(
+    id: String,
+    _jwt: MiddlewareGuard<JwtAuthentication>,
+    _auth: MiddlewareGuard<RoleAuthorization<Admin>>,
+    request: Validated<Json<ResetPasswordRequest>>,
+    user_service: &State<Arc<UserService>>,
+) -> Result<Status, ApiError> {
+    let request = request.into_inner().into_inner();
+
+    user_service
+        .reset_password(id, request.new_password)
+        .await?;
+
+    info!("Password reset by administrator");
+    Ok(Status::NoContent)
+}
+
+/// `user` as `V` renders it, tagged with that representation.
+fn tagged<V: Representation>(user: User) -> Versioned<Json<UserDTO>> {
+    let version = user.version;
+    let dto = V::user(user);
+    let tag = EntityTag::new(version, &dto);
 
-    Ok(Json(user))
+    Versioned(Json(dto), tag)
 }
@@ -0,0 +1,4 @@
+//! The version-specific routes as v1 serves them. Users carry no id and
+//! login returns a bare token.
+
+super::versioned::version_routes!(V1, rank = 1, login = LoginResponse);
@@ -0,0 +1,4 @@
+//! The version-specific routes as v2 serves them. Users carry their id and
+//! login returns an OAuth-style token response.
+
+super::versioned::version_routes!(V2, rank = 2, login = TokenResponse);
@@ -0,0 +1,162 @@
+//! Routes whose representation changed between versions. Each version
+//! mounts its own copy, declared once here: the handler bodies in
+//! [`super::user`] and [`super::auth`] are generic over the version, and
+//! [`Representation`](crate::api::version::Representation) maps users and
+//! login tokens to each version's DTOs.
+//!
+//! Every version is also mounted on `/api`, so each copy gets its own rank;
+//! [`Serves`](crate::api::version::Serves) forwards the requests that
+//! negotiated another version to the next rank.
+
+/// Declares the version-specific routes for `$version`, ranked `$rank`,
+/// with `$login` as its login response.
+macro_rules! version_routes {
+    ($version:ident, rank = $rank:literal, login = $login:ident) => {
+        use std::{net::IpAddr, sync::Arc};
+
+        use rocket::{Route, State, get, patch, post, put, serde::json::Json};
+
+        use $crate::{
+            api::{
+                error::{ApiError, ProblemDetails},
+                requests::{
+                    PageConfig,
+                    auth_reqs::{$login, LoginRequest},
+                    conditional::{IfMatch, IfNoneMatch},
+                    validated::Validated,
+                },
+                responses::{
+                    conditional::{Conditional, Versioned},
+                    user::UserDTO,
+                },
+                routes::{auth, user},
+                version::{$version, Representation, Serves},
+            },
+            auth::service::AuthService,
+            config::settings::Settings,
+            core::user::{
+                dto::{UpdateUser, UserPatch},
+                service::UserService,
+            },
+        };
+
+        pub fn routes() -> Vec<Route> {
+            rocket::routes![get_all_users, get_user, update_user, patch_user, login]
+        }
+
+        #[utoipa::path(
+            tag = "users",
+            params(PageConfig),
+            responses(
+                (status = 200, description = "Page of users", body = Vec<UserDTO>),
+                (status = 422, description = "Invalid paging or filter parameters", body = ProblemDetails, content_type = "application/problem+json"),
+            )
+        )]
+        #[get("/users?<spec..>", rank = $rank)]
+        async fn get_all_users(
+            _version: Serves<$version>,
+            spec: PageConfig,
+            user_service: &State<Arc<UserService>>,
+        ) -> Result<Json<Vec<UserDTO>>, ApiError> {
+            user::get_all_users::<$version>(spec, user_service).await
+        }
+
+        #[utoipa::path(
+            tag = "users",
+            params(("If-None-Match" = Option<String>, Header, description = "ETag of a cached representation")),
+            responses(
+                (status = 200, description = "User", body = UserDTO, headers(("ETag" = String))),
+                (status = 304, description = "Cached representation is current"),
+                (status = 404, description = "User not found", body = ProblemDetails, content_type = "application/problem+json"),
+            )
+        )]
+        #[get("/users/<id>", rank = $rank)]
+        async fn get_user(
+            _version: Serves<$version>,
+            id: String,
+            if_none_match: IfNoneMatch,
+            user_service: &State<Arc<UserService>>,
+        ) -> Result<Conditional<Json<UserDTO>>, ApiError> {
+            user::get_user::<$version>(id, if_none_match, user_service).await
+        }
+
+        #[utoipa::path(
+            tag = "users",
+            params(("If-Match" = Option<String>, Header, description = "ETag the user must still have")),
+            request_body = UpdateUser,
+            responses(
+                (status = 200, description = "Updated user", body = UserDTO, headers(("ETag" = String))),
+                (status = 404, description = "User not found", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 409, description = "Email is already taken", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 412, description = "User was modified", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 422, description = "Invalid fields or password policy violation", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 428, description = "If-Match is required", body = ProblemDetails, content_type = "application/problem+json"),
+            )
+        )]
+        #[put("/users?<id>", data = "<user_data>", rank = $rank)]
+        async fn update_user(
+            _version: Serves<$version>,
+            id: String,
+            user_data: Validated<Json<UpdateUser>>,
+            if_match: IfMatch,
+            user_service: &State<Arc<UserService>>,
+            settings: &State<Settings>,
+        ) -> Result<Versioned<Json<UserDTO>>, ApiError> {
+            user::update_user::<$version>(id, user_data, if_match, user_service, settings).await
+        }
+
+        #[utoipa::path(
+            tag = "users",
+            params(("If-Match" = Option<String>, Header, description = "ETag the user must still have")),
+            request_body(content(
+                (Object = "application/merge-patch+json"),
+                (json_patch::Patch = "application/json-patch+json"),
+            )),
+            responses(
+                (status = 200, description = "Patched user", body = UserDTO, headers(("ETag" = String))),
+                (status = 404, description = "User not found", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 409, description = "A `test` operation failed, or the email is already taken", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 412, description = "User was modified", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 415, description = "Unsupported patch media type", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 422, description = "Patch could not be applied or produced invalid fields", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 428, description = "If-Match is required", body = ProblemDetails, content_type = "application/problem+json"),
+            )
+        )]
+        #[patch("/users/<id>", data = "<patch>", rank = $rank)]
+        async fn patch_user(
+            _version: Serves<$version>,
+            id: String,
+            patch: UserPatch,
+            if_match: IfMatch,
+            user_service: &State<Arc<UserService>>,
+            settings: &State<Settings>,
+        ) -> Result<Versioned<Json<UserDTO>>, ApiError> {
+            user::patch_user::<$version>(id, patch, if_match, user_service, settings).await
+        }
+
+        #[utoipa::path(
+            tag = "auth",
+            request_body = LoginRequest,
+            responses(
+                (status = 200, description = "Bearer token", body = $login),
+                (status = 401, description = "Invalid email or password", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 422, description = "Invalid fields", body = ProblemDetails, content_type = "application/problem+json"),
+                (status = 503, description = "Database unavailable", body = ProblemDetails, content_type = "application/problem+json"),
+            )
+        )]
+        #[post("/auth", data = "<credentials>", rank = $rank)]
+        async fn login(
+            _version: Serves<$version>,
+            credentials: Validated<Json<LoginRequest>>,
+            client_ip: Option<IpAddr>,
+            auth_service: &State<Arc<AuthService>>,
+            settings: &State<Settings>,
+        ) -> Result<Json<$login>, ApiError> {
+            let token = auth::login(credentials, client_ip, auth_service).await?;
+
+            Ok(Json($version::login(token, settings)))
+        }
+    };
+}
+
+pub(super) use version_routes;
@@ -0,0 +1,294 @@
+use std::marker::PhantomData;
+
+use chrono::{DateTime, Utc};
+use rocket::{
+    Request, Response,
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Status},
+    request::{FromRequest, Outcome},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        error::{ApiError, remember_guard_failure},
+        requests::auth_reqs::{LoginResponse, TokenResponse},
+        responses::user::UserDTO,
+    },
+    config::settings::{Settings, VersionDeprecation},
+    core::user::model::User,
+};
+
+/// Vendor media type prefix accepted for version negotiation, as in
+/// `application/vnd.user-api.v2+json`.
+const VENDOR_PREFIX: &str = "vnd.user-api.";
+
+/// Version of the HTTP API a request is served by. Every version is mounted
+/// under its own base; the unversioned `/api` base negotiates one from the
+/// `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+
+    pub fn base(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1",
+            ApiVersion::V2 => "/api/v2",
+        }
+    }
+
+    pub fn latest() -> ApiVersion {
+        ApiVersion::V2
+    }
+
+    fn parse(version: &str) -> Option<ApiVersion> {
+        let version = version.strip_prefix('v').unwrap_or(version);
+        ApiVersion::ALL
+            .into_iter()
+            .find(|candidate| &candidate.as_str()[1..] == version)
+    }
+}
+
+/// Where the version of a request came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Path,
+    Accept,
+    Default,
+}
+
+struct ResolvedVersion(Result<(ApiVersion, Source), ApiError>);
+
+fn resolve<'r>(request: &'r Request<'_>) -> &'r Result<(ApiVersion, Source), ApiError> {
+    &request
+        .local_cache(|| ResolvedVersion(resolve_uncached(request)))
+        .0
+}
+
+fn resolve_uncached(request: &Request<'_>) -> Result<(ApiVersion, Source), ApiError> {
+    let mut segments = request.uri().path().segments();
+
+    if segments.next() == Some("api")
+        && let Some(version) = segments.next().and_then(path_version)
+    {
+        return Ok((version, Source::Path));
+    }
+
+    if let Some(version) = accept_version(request)? {
+        return Ok((version, Source::Accept));
+    }
+
+    let default = request
+        .rocket()
+        .state::<Settings>()
+        .map(|settings| settings.versioning.default_version)
+        .unwrap_or(ApiVersion::V1);
+
+    Ok((default, Source::Default))
+}
+
+fn path_version(segment: &str) -> Option<ApiVersion> {
+    segment
+        .starts_with('v')
+        .then(|| ApiVersion::parse(segment))
+        .flatten()
+}
+
+/// Reads a version from `Accept`, either as a `version` parameter
+/// (`application/json; version=2`) or a vendor media type.
+fn accept_version(request: &Request<'_>) -> Result<Option<ApiVersion>, ApiError> {
+    let Some(accept) = request.accept() else {
+        return Ok(None);
+    };
+
+    for media_type in accept.iter() {
+        let requested = media_type
+            .params()
+            .find(|(name, _)| *name == "version")
+            .map(|(_, value)| value.to_string())
+            .or_else(|| {
+                media_type
+                    .sub()
+                    .as_str()
+                    .strip_prefix(VENDOR_PREFIX)
+                    .map(|rest| rest.split('+').next().unwrap_or_default().to_string())
+            });
+
+        if let Some(requested) = requested {
+            return ApiVersion::parse(&requested).map(Some).ok_or_else(|| {
+                ApiError::new(
+                    Status::NotAcceptable,
+                    "unsupported-api-version",
+                    format!("API version `{requested}` is not supported"),
+                )
+            });
+        }
+    }
+
+    Ok(None)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiVersion {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match resolve(request) {
+            Ok((version, _)) => Outcome::Success(*version),
+            Err(e) => {
+                remember_guard_failure(request, e.clone());
+                Outcome::Error((e.status(), e.clone()))
+            }
+        }
+    }
+}
+
+/// A version as a type, naming the route set a handler belongs to.
+pub trait VersionMarker: Send + Sync + 'static {
+    const VERSION: ApiVersion;
+}
+
+pub struct V1;
+
+impl VersionMarker for V1 {
+    const VERSION: ApiVersion = ApiVersion::V1;
+}
+
+pub struct V2;
+
+impl VersionMarker for V2 {
+    const VERSION: ApiVersion = ApiVersion::V2;
+}
+
+/// How a version renders the resources whose representation changed between
+/// versions.
+pub trait Representation: VersionMarker {
+    type Login: Serialize;
+
+    fn user(user: User) -> UserDTO {
+        UserDTO::versioned(user, Self::VERSION)
+    }
+
+    fn login(token: String, settings: &Settings) -> Self::Login;
+}
+
+impl Representation for V1 {
+    type Login = LoginResponse;
+
+    fn login(token: String, _settings: &Settings) -> LoginResponse {
+        LoginResponse { token }
+    }
+}
+
+impl Representation for V2 {
+    type Login = TokenResponse;
+
+    fn login(token: String, settings: &Settings) -> TokenResponse {
+        TokenResponse {
+            access_token: token,
+            token_type: "Bearer",
+            expires_in: settings.jwt.expiration,
+        }
+    }
+}
+
+/// Admits requests served by version `V`. Every version's route set is also
+/// mounted on `/api`; there a request for another version is forwarded to
+/// that version's handler.
+pub struct Serves<V: VersionMarker>(PhantomData<V>);
+
+#[rocket::async_trait]
+impl<'r, V: VersionMarker> FromRequest<'r> for Serves<V> {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match resolve(request) {
+            Ok((version, _)) if *version == V::VERSION => Outcome::Success(Serves(PhantomData)),
+            Ok(_) => Outcome::Forward(Status::NotFound),
+            Err(e) => {
+                remember_guard_failure(request, e.clone());
+                Outcome::Error((e.status(), e.clone()))
+            }
+        }
+    }
+}
+
+/// Tags API responses with the version that served them and, for deprecated
+/// versions, the `Deprecation`, `Sunset` and successor `Link` headers.
+pub struct ApiVersioning;
+
+#[rocket::async_trait]
+impl Fairing for ApiVersioning {
+    fn info(&self) -> Info {
+        Info {
+            name: "API versioning headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if request.uri().path().segments().next() != Some("api") {
+            return;
+        }
+
+        let Ok((version, source)) = resolve(request) else {
+            return;
+        };
+
+        response.set_header(Header::new("Api-Version", version.as_str()));
+
+        if *source != Source::Path {
+            response.adjoin_header(Header::new("Vary", "Accept"));
+        }
+
+        let deprecation = request.rocket().state::<Settings>().and_then(|settings| {
+            settings
+                .versioning
+                .deprecated
+                .iter()
+                .find(|deprecation| deprecation.version == *version)
+        });
+
+        if let Some(deprecation) = deprecation {
+            add_deprecation_headers(response, deprecation);
+        }
+    }
+}
+
+fn add_deprecation_headers(response: &mut Response<'_>, deprecation: &VersionDeprecation) {
+    let deprecated = deprecation
+        .since
+        .map(|since| format!("@{}", since.timestamp()))
+        .unwrap_or_else(|| "true".to_string());
+
+    response.set_header(Header::new("Deprecation", deprecated));
+
+    if let Some(sunset) = deprecation.sunset {
+        response.set_header(Header::new("Sunset", http_date(sunset)));
+    }
+
+    response.adjoin_header(Header::new(
+        "Link",
+        format!(
+            "<{}>; rel=\"successor-version\"",
+            ApiVersion::latest().base()
+        ),
+    ));
+}
+
+fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
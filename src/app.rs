@@ -1,22 +1,45 @@
 use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use config::ConfigError;
 use rocket::Build;
 use rocket::Config;
 use rocket::Rocket;
+use rocket::Route;
+use rocket::Shutdown;
+use rocket::fairing::AdHoc;
 use rocket_cors::AllowedHeaders;
 use rocket_cors::AllowedOrigins;
 use rocket_cors::Method;
 use thiserror::Error;
+use tracing::{error, info};
 
-use crate::api::routes::get_routes;
+use crate::api::access_log::AccessLog;
+use crate::api::error::catchers;
+use crate::api::request_id::{REQUEST_ID_HEADER, RequestIds, traced};
+use crate::api::routes::{docs, health, logging, negotiated_routes, versioned_routes};
+use crate::api::version::{ApiVersion, ApiVersioning};
 use crate::auth::service::AuthService;
-use crate::config::load_settings;
+use crate::config::settings::{DatabaseBackend, Settings};
+use crate::core::user::hashing::PasswordHasher;
+use crate::core::user::import::ImportJobs;
+use crate::core::user::password_policy::PasswordPolicy;
+use crate::core::user::repo::UserRepository;
 use crate::core::user::service::UserService;
-use crate::infra::db::connection::create_surreal_client;
+use crate::infra::db::cached_repo::CachedUserRepository;
+use crate::infra::db::circuit_breaker::CircuitBreaker;
+use crate::infra::db::connection::{create_surreal_client, supervise};
+use crate::infra::db::memory_repo::InMemoryUserRepository;
+use crate::infra::db::metered_repo::MeteredUserRepository;
+use crate::infra::db::migrations::{MigrationError, migrate_on_startup};
+use crate::infra::db::resilient_repo::ResilientUserRepository;
+use crate::infra::db::sql_connection::{create_sql_pool, migrate_sql_on_startup};
+use crate::infra::db::sql_repo::SqlUserRepository;
 use crate::infra::db::user_repo::SurrealUserRepository;
+use crate::infra::health::{Database, HealthCheck};
+use crate::metrics::{HttpMetrics, metrics_endpoint};
 
 pub type AppRocket = Rocket<Build>;
 
@@ -30,14 +53,54 @@ pub enum ApplicationError {
 
     #[error("Failed to create CORS configuration")]
     CorsConfiguration(String),
-}
 
-pub async fn build_app() -> Result<AppRocket, ApplicationError> {
-    let cfg = load_settings().map_err(ApplicationError::ConfigurationParsing)?;
+    #[error("Failed to load password policy: {0}")]
+    PasswordPolicy(String),
+
+    #[error("Invalid password hashing configuration: {0}")]
+    PasswordHashing(String),
 
-    let database_conn = create_surreal_client(&cfg.surrealdb).await?;
-    let user_repo = SurrealUserRepository::new(Arc::clone(&database_conn));
-    let user_service = Arc::new(UserService::new(Arc::new(user_repo)));
+    #[error("{0}")]
+    Migration(#[from] MigrationError),
+
+    #[error("Pending migrations in strict mode, run `api migrate` first: {0}")]
+    PendingMigrations(String),
+}
+
+pub async fn build_app(cfg: Settings) -> Result<AppRocket, ApplicationError> {
+    let (database, repository) = connect_database(&cfg).await?;
+    let surreal_client = match &database {
+        Database::SurrealDb(client) => Some(Arc::clone(client)),
+        Database::Sql(_) | Database::Memory => None,
+    };
+    let health_check = Arc::new(HealthCheck::new(database, &cfg));
+    let resilience = &cfg.surrealdb.resilience;
+    let breaker = Arc::new(CircuitBreaker::new(
+        resilience.failure_threshold,
+        Duration::from_millis(resilience.open_duration_ms),
+    ));
+    let resilient_repo = ResilientUserRepository::new(
+        repository,
+        Arc::clone(&breaker),
+        Duration::from_millis(resilience.query_timeout_ms),
+        Duration::from_millis(resilience.write_timeout_ms),
+    );
+    let metered_repo: Arc<dyn UserRepository + Send + Sync> =
+        Arc::new(MeteredUserRepository::new(Arc::new(resilient_repo)));
+    let user_repo: Arc<dyn UserRepository + Send + Sync> = if cfg.user_cache.enabled {
+        Arc::new(CachedUserRepository::new(
+            metered_repo,
+            Duration::from_millis(cfg.user_cache.ttl_ms),
+            cfg.user_cache.max_entries,
+        ))
+    } else {
+        metered_repo
+    };
+    let password_policy = PasswordPolicy::new(cfg.password_policy.clone())
+        .map_err(|e| ApplicationError::PasswordPolicy(e.to_string()))?;
+    let hasher = PasswordHasher::new(&cfg.password_hashing)
+        .map_err(|e| ApplicationError::PasswordHashing(e.to_string()))?;
+    let user_service = Arc::new(UserService::new(user_repo, password_policy, hasher));
 
     let auth_service = Arc::new(AuthService::new(Arc::clone(&user_service), cfg.jwt.clone()));
 
@@ -47,6 +110,7 @@ pub async fn build_app() -> Result<AppRocket, ApplicationError> {
         "Accept",
         "Content-Type",
         "X-Requested-With",
+        REQUEST_ID_HEADER,
     ]);
 
     let allowed_methods: HashSet<Method> = cfg
@@ -64,6 +128,7 @@ pub async fn build_app() -> Result<AppRocket, ApplicationError> {
         allowed_headers,
         allow_credentials: true,
         allowed_methods,
+        expose_headers: HashSet::from([REQUEST_ID_HEADER.to_string()]),
         ..Default::default()
     }
     .to_cors()
@@ -71,14 +136,711 @@ pub async fn build_app() -> Result<AppRocket, ApplicationError> {
         ApplicationError::CorsConfiguration(format!("CORS config error: {:?}", e.to_string()))
     })?;
 
-    Ok(rocket::custom(Config {
+    let access_log = AccessLog::new(cfg.access_log.clone());
+    let surrealdb_cfg = cfg.surrealdb.clone();
+    let admin_config = cfg.server.admin_port.map(|port| Config {
+        port,
+        address: cfg.server.address,
+        shutdown: shutdown_config(),
+        ..Default::default()
+    });
+
+    let drain_delay = Duration::from_millis(cfg.server.drain_delay_ms);
+
+    let app = rocket::custom(Config {
         port: cfg.server.port,
         address: cfg.server.address,
+        shutdown: shutdown_config(),
         ..Default::default()
     })
     .manage(Arc::clone(&user_service))
     .manage(Arc::clone(&auth_service))
+    .manage(Arc::new(ImportJobs::default()))
+    .manage(health_check)
     .manage(cfg)
-    .mount("/api", get_routes())
-    .attach(cors))
+    .mount("/api", traced(negotiated_routes()))
+    .mount(
+        ApiVersion::V1.base(),
+        traced(versioned_routes(ApiVersion::V1)),
+    )
+    .mount(
+        ApiVersion::V2.base(),
+        traced(versioned_routes(ApiVersion::V2)),
+    )
+    .mount("/", traced(docs::routes()))
+    .mount("/", health::routes())
+    .register("/", catchers())
+    .attach(RequestIds)
+    .attach(access_log)
+    .attach(ApiVersioning)
+    .attach(HttpMetrics)
+    .attach(cors)
+    .attach(AdHoc::on_liftoff("Drain on shutdown", move |rocket| {
+        Box::pin(async move {
+            if let Some(health_check) = rocket.state::<Arc<HealthCheck>>() {
+                rocket::tokio::spawn(drain_on_signal(
+                    termination(),
+                    Arc::clone(health_check),
+                    rocket.shutdown(),
+                    drain_delay,
+                ));
+            }
+        })
+    }))
+    .attach(AdHoc::on_liftoff("Database supervisor", |_| {
+        Box::pin(async move {
+            if let Some(client) = surreal_client {
+                rocket::tokio::spawn(supervise(client, surrealdb_cfg, breaker));
+            }
+        })
+    }));
+
+    Ok(match admin_config {
+        Some(config) => app.attach(AdHoc::on_liftoff("Admin server", |rocket| {
+            let shutdown = rocket.shutdown();
+            Box::pin(async move {
+                rocket::tokio::spawn(serve_admin(config, auth_service, shutdown));
+            })
+        })),
+        None => app.mount("/", admin_routes()),
+    })
+}
+
+/// Graceful shutdown settings for every listener. Signals are handled by
+/// [`drain_on_signal`] instead, so each server shuts down once draining ends.
+fn shutdown_config() -> rocket::config::Shutdown {
+    rocket::config::Shutdown {
+        ctrlc: false,
+        #[cfg(unix)]
+        signals: HashSet::new(),
+        ..Default::default()
+    }
+}
+
+/// Fails readiness as soon as `signal` arrives, then waits `drain_delay`
+/// before starting Rocket's graceful shutdown, so load balancers stop
+/// routing here while requests are still being served.
+async fn drain_on_signal(
+    signal: impl Future<Output = ()>,
+    health_check: Arc<HealthCheck>,
+    shutdown: Shutdown,
+    drain_delay: Duration,
+) {
+    signal.await;
+    info!(?drain_delay, "Shutdown requested, draining");
+
+    health_check.begin_shutdown();
+    rocket::tokio::time::sleep(drain_delay).await;
+    shutdown.notify();
+}
+
+/// Resolves on SIGTERM or Ctrl-C.
+async fn termination() {
+    #[cfg(unix)]
+    {
+        use rocket::tokio::signal::unix::{SignalKind, signal};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            rocket::tokio::select! {
+                _ = terminate.recv() => {}
+                _ = rocket::tokio::signal::ctrl_c() => {}
+            }
+            return;
+        }
+    }
+
+    let _ = rocket::tokio::signal::ctrl_c().await;
+}
+
+/// Connects the configured backend and brings its schema up to date.
+async fn connect_database(
+    cfg: &Settings,
+) -> Result<(Database, Arc<dyn UserRepository + Send + Sync>), ApplicationError> {
+    Ok(match cfg.database.backend {
+        DatabaseBackend::SurrealDb => {
+            let client = create_surreal_client(&cfg.surrealdb).await?;
+            migrate_on_startup(&client, &cfg.surrealdb.migrations).await?;
+            (
+                Database::SurrealDb(Arc::clone(&client)),
+                Arc::new(SurrealUserRepository::new(client)),
+            )
+        }
+        DatabaseBackend::Sqlite | DatabaseBackend::Postgres => {
+            let pool = create_sql_pool(cfg.database.backend, &cfg.database.sql).await?;
+            migrate_sql_on_startup(&pool, cfg.database.sql.migrations).await?;
+            (
+                Database::Sql(pool.clone()),
+                Arc::new(SqlUserRepository::new(pool)),
+            )
+        }
+        DatabaseBackend::Memory => (Database::Memory, Arc::new(InMemoryUserRepository::new())),
+    })
+}
+
+/// Operational endpoints, served next to the API unless an admin port is set.
+fn admin_routes() -> Vec<Route> {
+    let mut routes = rocket::routes![metrics_endpoint];
+    routes.extend(logging::routes());
+    routes
+}
+
+/// Serves the admin routes on their own port until the main server, whose
+/// shutdown is `main`, starts its graceful shutdown after draining.
+async fn serve_admin(config: Config, auth_service: Arc<AuthService>, main: Shutdown) {
+    info!(port = config.port, "Starting admin server");
+
+    let admin = match rocket::custom(config)
+        .manage(auth_service)
+        .mount("/", admin_routes())
+        .register("/", catchers())
+        .ignite()
+        .await
+    {
+        Ok(admin) => admin,
+        Err(e) => {
+            error!("Admin server failed: {e}");
+            return;
+        }
+    };
+
+    let shutdown = admin.shutdown();
+    rocket::tokio::spawn(async move {
+        main.await;
+        shutdown.notify();
+    });
+
+    if let Err(e) = admin.launch().await {
+        error!("Admin server failed: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::{ContentType, Header, Method, Status},
+        local::asynchronous::Client,
+        tokio::time::timeout,
+    };
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn settings() -> Settings {
+        serde_json::from_value(json!({
+            "database": { "backend": "memory" },
+            "server": {
+                "port": 0,
+                "address": "127.0.0.1",
+                "allowed_origins": [],
+                "allowed_methods": ["GET", "POST"],
+            },
+            "jwt": { "secret": "test-secret", "expiration": 3600 },
+            "password_hashing": { "memory_kib": 64, "iterations": 1 },
+        }))
+        .unwrap()
+    }
+
+    async fn post_json(client: &Client, uri: &str, body: Value) -> Value {
+        let response = client
+            .post(uri.to_string())
+            .header(ContentType::JSON)
+            .body(body.to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok, "POST {uri}");
+        response.into_json().await.unwrap()
+    }
+
+    async fn get_json(client: &Client, uri: &str) -> Value {
+        let response = client.get(uri.to_string()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "GET {uri}");
+        response.into_json().await.unwrap()
+    }
+
+    /// Creates an administrator and returns a bearer token for it.
+    async fn admin_token(client: &Client) -> String {
+        let credentials = json!({
+            "email": "root@example.com",
+            "password": "correct horse battery staple",
+        });
+        let mut admin = credentials.clone();
+        admin["username"] = json!("root");
+        admin["roles"] = json!(["Admin"]);
+        post_json(client, "/api/users", admin).await;
+        let login = post_json(client, "/api/v2/auth", credentials).await;
+        login["access_token"].as_str().unwrap().to_string()
+    }
+
+    #[rocket::async_test]
+    async fn serves_v1_and_v2_side_by_side() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+
+        let created = post_json(
+            &client,
+            "/api/users",
+            json!({
+                "username": "ana",
+                "email": "ana@example.com",
+                "password": "correct horse battery staple",
+                "roles": ["User"],
+            }),
+        )
+        .await;
+        let id = created["id"].as_str().unwrap();
+
+        let credentials = json!({
+            "email": "ana@example.com",
+            "password": "correct horse battery staple",
+        });
+        let v1_login = post_json(&client, "/api/v1/auth", credentials.clone()).await;
+        let v2_login = post_json(&client, "/api/v2/auth", credentials).await;
+        assert!(v1_login["token"].is_string());
+        assert!(v1_login.get("access_token").is_none());
+        assert!(v2_login["access_token"].is_string());
+        assert_eq!(v2_login["token_type"], "Bearer");
+
+        let v1_user = get_json(&client, &format!("/api/v1/users/{id}")).await;
+        let v2_user = get_json(&client, &format!("/api/v2/users/{id}")).await;
+        assert!(v1_user.get("id").is_none());
+        assert_eq!(v2_user["id"], id);
+        assert_eq!(v1_user["email"], v2_user["email"]);
+
+        let negotiated = client
+            .get(format!("/api/users/{id}"))
+            .header(Header::new("Accept", "application/vnd.user-api.v2+json"))
+            .dispatch()
+            .await;
+        assert_eq!(negotiated.status(), Status::Ok);
+        let negotiated: Value = negotiated.into_json().await.unwrap();
+        assert_eq!(negotiated["id"], id);
+    }
+
+    #[rocket::async_test]
+    async fn echoes_or_generates_request_ids() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+
+        let echoed = client
+            .get("/health/live")
+            .header(Header::new(REQUEST_ID_HEADER, "req-42"))
+            .dispatch()
+            .await;
+        assert_eq!(echoed.headers().get_one(REQUEST_ID_HEADER), Some("req-42"));
+
+        let mut generated = HashSet::new();
+        let too_long = "x".repeat(129);
+        for header in [None, Some("has spaces"), Some(too_long.as_str())] {
+            let mut request = client.get("/health/live");
+            if let Some(id) = header {
+                request = request.header(Header::new(REQUEST_ID_HEADER, id.to_string()));
+            }
+            let response = request.dispatch().await;
+            let id = response.headers().get_one(REQUEST_ID_HEADER).unwrap();
+            assert!(uuid::Uuid::parse_str(id).is_ok(), "{id}");
+            generated.insert(id.to_string());
+        }
+        assert_eq!(generated.len(), 3, "generated ids are unique");
+    }
+
+    #[rocket::async_test]
+    async fn exposes_request_metrics_by_route_and_status() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+
+        for uri in ["/api/v2/users/nobody", "/no/such/page"] {
+            let response = client.get(uri).dispatch().await;
+            assert_eq!(response.status(), Status::NotFound);
+        }
+
+        let response = client.get("/metrics").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("text", "plain").with_params(("version", "0.0.4")))
+        );
+        let body = response.into_string().await.unwrap();
+
+        // Other tests share the registry, so counts are only bounded below.
+        let sample = |series: &str| -> f64 {
+            body.lines()
+                .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+                .unwrap_or_else(|| panic!("no `{series}` sample in:\n{body}"))
+                .parse()
+                .unwrap()
+        };
+        for labels in [
+            r#"method="GET",route="/api/v2/users/<id>",status="404""#,
+            r#"method="GET",route="unmatched",status="404""#,
+        ] {
+            assert!(sample(&format!("http_requests_total{{{labels}}}")) >= 1.0);
+            assert!(sample(&format!("http_request_duration_seconds_count{{{labels}}}")) >= 1.0);
+            assert!(
+                sample(&format!(
+                    "http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}}"
+                )) >= 1.0
+            );
+        }
+    }
+
+    #[rocket::async_test]
+    async fn rejects_a_doubled_version_prefix() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+
+        for accept in [
+            "application/json; version=vv2",
+            "application/vnd.user-api.vv2+json",
+        ] {
+            let response = client
+                .get("/api/users")
+                .header(Header::new("Accept", accept))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::NotAcceptable, "{accept}");
+        }
+
+        for accept in [
+            "application/json; version=v2",
+            "application/json; version=2",
+        ] {
+            let response = client
+                .get("/api/users")
+                .header(Header::new("Accept", accept))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok, "{accept}");
+            assert_eq!(response.headers().get_one("Api-Version"), Some("v2"));
+        }
+
+        let response = client.get("/api/vv2/users").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn reports_every_invalid_field_at_once() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+
+        let response = client
+            .post("/api/users")
+            .header(ContentType::JSON)
+            .body(
+                json!({
+                    "username": "ana",
+                    "email": "not an email",
+                    "password": "short",
+                    "roles": ["User"],
+                })
+                .to_string(),
+            )
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        let problem: Value = response.into_json().await.unwrap();
+        let fields: Vec<(&str, &str)> = problem["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| {
+                let field = error["field"].as_str().unwrap();
+                (field, error["code"].as_str().unwrap())
+            })
+            .collect();
+        assert_eq!(fields, [("email", "email"), ("password", "too_short")]);
+    }
+
+    #[rocket::async_test]
+    async fn patches_only_writable_fields() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+
+        let created = post_json(
+            &client,
+            "/api/users",
+            json!({
+                "username": "ana",
+                "email": "ana@example.com",
+                "password": "correct horse battery staple",
+                "roles": ["User"],
+            }),
+        )
+        .await;
+        let uri = format!("/api/v2/users/{}", created["id"].as_str().unwrap());
+        let json_patch = ContentType::new("application", "json-patch+json");
+        let merge_patch = ContentType::new("application", "merge-patch+json");
+        let patch = |media: &ContentType, body: Value| {
+            client
+                .patch(uri.clone())
+                .header(media.clone())
+                .body(body.to_string())
+                .dispatch()
+        };
+
+        let failed_test = patch(
+            &json_patch,
+            json!([
+                { "op": "test", "path": "/username", "value": "bea" },
+                { "op": "replace", "path": "/username", "value": "cid" },
+            ]),
+        )
+        .await;
+        assert_eq!(failed_test.status(), Status::Conflict);
+
+        for (media, body) in [
+            (
+                &json_patch,
+                json!([{ "op": "replace", "path": "/roles", "value": [] }]),
+            ),
+            (
+                &json_patch,
+                json!([{ "op": "add", "path": "/roles", "value": ["Admin"] }]),
+            ),
+            (
+                &json_patch,
+                json!([{ "op": "remove", "path": "/username" }]),
+            ),
+            (&merge_patch, json!({ "roles": ["Admin"] })),
+            (&merge_patch, json!({ "email": null })),
+        ] {
+            let rejected = patch(media, body.clone()).await;
+            assert_eq!(rejected.status(), Status::UnprocessableEntity, "{body}");
+        }
+
+        let before = get_json(&client, &uri).await;
+        for (media, body) in [
+            (&merge_patch, json!({ "username": "ana" })),
+            (
+                &json_patch,
+                json!([{ "op": "test", "path": "/email", "value": "ana@example.com" }]),
+            ),
+        ] {
+            let unchanged = patch(media, body).await;
+            assert_eq!(unchanged.status(), Status::Ok);
+            let unchanged: Value = unchanged.into_json().await.unwrap();
+            assert_eq!(unchanged, before, "a no-op patch must not bump the version");
+        }
+
+        let renamed = patch(&merge_patch, json!({ "username": "bea" })).await;
+        assert_eq!(renamed.status(), Status::Ok);
+        let renamed: Value = renamed.into_json().await.unwrap();
+        assert_eq!(renamed["username"], "bea");
+        assert_eq!(renamed["version"], before["version"].as_u64().unwrap() + 1);
+    }
+
+    #[rocket::async_test]
+    async fn import_location_follows_the_version_base() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+
+        let token = admin_token(&client).await;
+
+        for base in ["/api", "/api/v1", "/api/v2"] {
+            let response = client
+                .post(format!("{base}/users/import"))
+                .header(ContentType::CSV)
+                .header(Header::new("Authorization", format!("Bearer {token}")))
+                .body("username,email,password,roles\n")
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Accepted);
+
+            let location = response.headers().get_one("Location").unwrap();
+            assert!(location.starts_with(&format!("{base}/users/import/")));
+        }
+    }
+
+    #[rocket::async_test]
+    async fn log_filter_reload_rejects_invalid_directives() {
+        let filter = crate::telemetry::test_log_filter();
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+        let authorization = Header::new(
+            "Authorization",
+            format!("Bearer {}", admin_token(&client).await),
+        );
+
+        let reloaded = client
+            .put("/log-filter")
+            .header(authorization.clone())
+            .body("warn,api=debug\n")
+            .dispatch()
+            .await;
+        assert_eq!(reloaded.status(), Status::Ok);
+        assert_eq!(reloaded.into_string().await.unwrap(), filter.current());
+        let applied = filter.current();
+        assert!(applied.contains("api=debug"), "{applied}");
+
+        let rejected = client
+            .put("/log-filter")
+            .header(authorization.clone())
+            .body("api=loud")
+            .dispatch()
+            .await;
+        assert_eq!(rejected.status(), Status::UnprocessableEntity);
+        let problem: Value = rejected.into_json().await.unwrap();
+        assert!(
+            problem["type"]
+                .as_str()
+                .unwrap()
+                .ends_with("invalid-log-filter")
+        );
+
+        let current = client
+            .get("/log-filter")
+            .header(authorization)
+            .dispatch()
+            .await;
+        assert_eq!(current.into_string().await.unwrap(), applied);
+
+        let anonymous = client.put("/log-filter").body("trace").dispatch().await;
+        assert_eq!(anonymous.status(), Status::Unauthorized);
+        assert_eq!(filter.current(), applied);
+    }
+
+    /// Operational routes outside the versioned API, left out of the
+    /// OpenAPI document on purpose.
+    const UNDOCUMENTED: [&str; 4] = ["/health/live", "/health/ready", "/metrics", "/log-filter"];
+
+    #[rocket::async_test]
+    async fn documents_every_mounted_route() {
+        let app = build_app(settings()).await.unwrap();
+        let document = crate::api::openapi::api_document();
+
+        for route in app.routes() {
+            let path = route.uri.path().to_string();
+            if path.starts_with("/api/openapi.json")
+                || path.starts_with("/api/docs")
+                || path.starts_with("/api/rapidoc")
+                || UNDOCUMENTED.contains(&path.as_str())
+            {
+                continue;
+            }
+
+            // Routes on `/api` are documented under every version base.
+            let documented: Vec<String> = match path.strip_prefix("/api/") {
+                Some(rest) if !rest.starts_with("v1/") && !rest.starts_with("v2/") => {
+                    ApiVersion::ALL
+                        .iter()
+                        .map(|version| format!("{}/{rest}", version.base()))
+                        .collect()
+                }
+                _ => vec![path.clone()],
+            };
+
+            for path in documented {
+                let template = path.replace('<', "{").replace('>', "}");
+                let item = document.paths.paths.get(&template);
+                let operation = item.and_then(|item| match route.method {
+                    Method::Get => item.get.as_ref(),
+                    Method::Put => item.put.as_ref(),
+                    Method::Post => item.post.as_ref(),
+                    Method::Delete => item.delete.as_ref(),
+                    Method::Patch => item.patch.as_ref(),
+                    _ => None,
+                });
+                assert!(
+                    operation.is_some(),
+                    "{} {template} is mounted but not documented",
+                    route.method
+                );
+            }
+        }
+    }
+
+    #[rocket::async_test]
+    async fn exports_in_creation_order_by_default() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+        let token = admin_token(&client).await;
+
+        let mut created = vec!["root".to_string()];
+        for n in 1..=6 {
+            let username = format!("user{n}");
+            post_json(
+                &client,
+                "/api/users",
+                json!({
+                    "username": username,
+                    "email": format!("{username}@example.com"),
+                    "password": "correct horse battery staple",
+                    "roles": ["User"],
+                }),
+            )
+            .await;
+            created.push(username);
+        }
+
+        let response = client
+            .get("/api/users/export?format=csv&columns=username")
+            .header(Header::new("Authorization", format!("Bearer {token}")))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        let exported: Vec<&str> = body.lines().skip(1).collect();
+        assert_eq!(exported, created);
+    }
+
+    #[rocket::async_test]
+    async fn readiness_fails_before_shutdown_starts() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+        let health_check = client.rocket().state::<Arc<HealthCheck>>().unwrap();
+        let shutdown = client.rocket().shutdown();
+
+        let ready = client.get("/health/ready").dispatch().await;
+        assert_eq!(ready.status(), Status::Ok);
+
+        // The signal has arrived; polling once gets the drain into its delay.
+        let drain = drain_on_signal(
+            async {},
+            Arc::clone(health_check),
+            shutdown.clone(),
+            Duration::from_millis(200),
+        );
+        let mut drain = Box::pin(drain);
+        assert!(timeout(Duration::ZERO, &mut drain).await.is_err());
+
+        let draining = client.get("/health/ready").dispatch().await;
+        assert_eq!(draining.status(), Status::ServiceUnavailable);
+        assert!(
+            timeout(Duration::ZERO, shutdown.clone()).await.is_err(),
+            "shutdown started before the drain delay"
+        );
+
+        timeout(Duration::from_secs(5), drain).await.unwrap();
+        timeout(Duration::ZERO, shutdown)
+            .await
+            .expect("shutdown did not start after the drain delay");
+    }
+
+    #[rocket::async_test]
+    async fn admin_server_stops_with_the_main_server() {
+        let app = build_app(settings()).await.unwrap();
+        let client = Client::tracked(app).await.unwrap();
+        let auth_service = client.rocket().state::<Arc<AuthService>>().unwrap();
+        let shutdown = client.rocket().shutdown();
+        let config = Config {
+            port: 0,
+            address: [127, 0, 0, 1].into(),
+            shutdown: shutdown_config(),
+            ..Config::debug_default()
+        };
+
+        let admin = rocket::tokio::spawn(serve_admin(
+            config,
+            Arc::clone(auth_service),
+            shutdown.clone(),
+        ));
+        rocket::tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!admin.is_finished());
+
+        shutdown.notify();
+        timeout(Duration::from_secs(5), admin)
+            .await
+            .expect("admin server outlived the main server")
+            .unwrap();
+    }
 }
@@ -11,7 +11,7 @@ use crate::{
     config::settings::JwtSettings,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
@@ -21,7 +21,22 @@ pub struct Claims {
 #[derive(Debug)]
 pub struct JwtAuthentication(pub Claims);
 
-#[derive(Debug, Error)]
+/// Outcome of validating the request's token, so guards that run again in the
+/// same request, like `RoleAuthorization`, reuse it.
+struct ValidatedToken(Result<Claims, (Status, JwtAuthenticationError)>);
+
+/// Subject of the token a request authenticated with, kept for logging.
+struct AuthenticatedSubject(Option<String>);
+
+/// Returns the `sub` of the request's token once JWT authentication succeeded.
+pub fn authenticated_subject<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request
+        .local_cache(|| AuthenticatedSubject(None))
+        .0
+        .as_deref()
+}
+
+#[derive(Debug, Clone, Error)]
 pub enum JwtAuthenticationError {
     #[error("Token has been expired")]
     ExpiredToken,
@@ -34,6 +49,9 @@ pub enum JwtAuthenticationError {
 
     #[error("User unauthorized")]
     Unauthorized,
+
+    #[error("Cannot verify the user while the database is unavailable")]
+    Unavailable,
 }
 
 #[async_trait]
@@ -41,25 +59,40 @@ impl Middleware for JwtAuthentication {
     type Error = JwtAuthenticationError;
 
     async fn from_request(request: &Request<'_>) -> Result<Self, (Status, Self::Error)> {
-        let auth_service = request
-            .rocket()
-            .state::<Arc<AuthService>>()
-            .ok_or((Status::Unauthorized, JwtAuthenticationError::Unauthorized))?;
-
-        let token = request
-            .headers()
-            .get_one("Authorization")
-            .and_then(|h| h.strip_prefix("Bearer "))
-            .ok_or((Status::Unauthorized, JwtAuthenticationError::MissingToken))?;
-
-        auth_service
-            .validate_token(token)
+        request
+            .local_cache_async(async { ValidatedToken(authenticate(request).await) })
             .await
+            .0
+            .clone()
             .map(Self)
-            .map_err(|e| (Status::Unauthorized, e))
     }
 }
 
+async fn authenticate(request: &Request<'_>) -> Result<Claims, (Status, JwtAuthenticationError)> {
+    let auth_service = request
+        .rocket()
+        .state::<Arc<AuthService>>()
+        .ok_or((Status::Unauthorized, JwtAuthenticationError::Unauthorized))?;
+
+    let token = request
+        .headers()
+        .get_one("Authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or((Status::Unauthorized, JwtAuthenticationError::MissingToken))?;
+
+    let claims = auth_service
+        .validate_token(token)
+        .await
+        .map_err(|e| match e {
+            JwtAuthenticationError::Unavailable => (Status::ServiceUnavailable, e),
+            e => (Status::Unauthorized, e),
+        })?;
+
+    request.local_cache(|| AuthenticatedSubject(Some(claims.sub.clone())));
+
+    Ok(claims)
+}
+
 pub(super) fn validate_jwt(
     token: &str,
     settings: &JwtSettings,
@@ -7,7 +7,7 @@ use rocket::{Request, async_trait, http::Status};
 
 pub struct RoleAuthorization<T: RequiredRole>(PhantomData<T>);
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, thiserror::Error)]
 pub enum RoleAuthorizationError {
     #[error("Unauthorized role")]
     Unauthorized,
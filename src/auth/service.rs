@@ -1,12 +1,14 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 use jsonwebtoken::{EncodingKey, Header, encode};
 use rocket::time::UtcDateTime;
+use tracing::{info, warn};
 
 use crate::{
     auth::jwt::{Claims, JwtAuthenticationError, validate_jwt},
     config::settings::JwtSettings,
-    core::user::{model::User, service::UserService},
+    core::user::{error::UserServiceError, model::User, service::UserService},
+    metrics::{InFlight, metrics},
 };
 
 pub struct AuthService {
@@ -19,14 +21,39 @@ impl AuthService {
         Self { user_service, jwt }
     }
 
-    pub async fn login(&self, email: String, password: String) -> Result<String, ()> {
-        let user = self
-            .user_service
-            .verify_user(email, password)
-            .await
-            .map_err(|_| ())?;
+    pub async fn login(
+        &self,
+        email: String,
+        password: String,
+        ip: Option<IpAddr>,
+    ) -> Result<String, UserServiceError> {
+        let result = self.authenticate(email, password).await;
 
-        self.generate_jwt(&user).map_err(|_| ())
+        let outcome = if result.is_ok() { "success" } else { "failure" };
+        metrics().logins.with_label_values(&[outcome]).inc();
+
+        let (user, token) = result?;
+
+        if let Err(e) = self.user_service.record_login(user.id.clone(), ip).await {
+            warn!(user_id = %user.id, "Failed to record login: {:?}", e);
+        }
+
+        info!(user_id = %user.id, "Login succeeded");
+        Ok(token)
+    }
+
+    async fn authenticate(
+        &self,
+        email: String,
+        password: String,
+    ) -> Result<(User, String), UserServiceError> {
+        let user = self.user_service.verify_user(email, password).await?;
+
+        let token = self
+            .generate_jwt(&user)
+            .map_err(|_| UserServiceError::Unknown)?;
+
+        Ok((user, token))
     }
 
     pub fn generate_jwt(&self, user: &User) -> Result<String, jsonwebtoken::errors::Error> {
@@ -49,12 +76,33 @@ impl AuthService {
     }
 
     pub async fn validate_token(&self, token: &str) -> Result<Claims, JwtAuthenticationError> {
+        let metrics = metrics();
+        let result = {
+            let _active = InFlight::track(&metrics.active_token_validations);
+            self.validate_claims(token).await
+        };
+
+        let outcome = match &result {
+            Ok(_) => "valid",
+            Err(JwtAuthenticationError::ExpiredToken) => "expired",
+            Err(_) => "invalid",
+        };
+        metrics
+            .token_validations
+            .with_label_values(&[outcome])
+            .inc();
+
+        result
+    }
+
+    async fn validate_claims(&self, token: &str) -> Result<Claims, JwtAuthenticationError> {
         let claims = validate_jwt(token, &self.jwt)?;
 
-        self.user_service
-            .find_by_id(claims.sub.clone())
-            .await
-            .map(|_| claims)
-            .ok_or(JwtAuthenticationError::Unauthorized)
+        match self.user_service.find_by_id(claims.sub.clone()).await {
+            Ok(Some(_)) => Ok(claims),
+            Ok(None) => Err(JwtAuthenticationError::Unauthorized),
+            Err(UserServiceError::Unavailable(_)) => Err(JwtAuthenticationError::Unavailable),
+            Err(_) => Err(JwtAuthenticationError::Unauthorized),
+        }
     }
 }
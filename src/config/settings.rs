@@ -1,21 +1,208 @@
 use std::net::IpAddr;
 
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 
+use crate::api::version::ApiVersion;
+
 #[derive(Deserialize, Debug)]
 pub struct Settings {
+    #[serde(default)]
+    pub database: DatabaseSettings,
+    /// Connection and migration settings apply to the `surrealdb` backend
+    /// only; `resilience` applies to every backend.
+    #[serde(default)]
     pub surrealdb: SurrealDbConfig,
     pub server: ServerConfig,
     pub jwt: JwtSettings,
+    #[serde(default)]
+    pub password_policy: PasswordPolicySettings,
+    #[serde(default)]
+    pub password_hashing: PasswordHashingSettings,
+    #[serde(default)]
+    pub versioning: VersioningSettings,
+    #[serde(default)]
+    pub access_log: AccessLogSettings,
+    #[serde(default)]
+    pub user_cache: UserCacheSettings,
+    #[serde(default)]
+    pub telemetry: TelemetrySettings,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    pub backend: DatabaseBackend,
+    pub sql: SqlSettings,
+}
+
+/// Where users are stored. `memory` keeps them in the process and loses them
+/// on restart.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    #[default]
+    SurrealDb,
+    Sqlite,
+    Postgres,
+    Memory,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SqlSettings {
+    /// `sqlite://...` or `postgres://...`, matching `database.backend`.
+    pub url: String,
+    pub max_connections: u32,
+    /// Same modes as `surrealdb.migrations.mode`, for `migrations/sql`.
+    pub migrations: MigrationMode,
+}
+
+impl Default for SqlSettings {
+    fn default() -> Self {
+        Self {
+            url: "sqlite://data/users.db?mode=rwc".to_string(),
+            max_connections: 10,
+            migrations: MigrationMode::default(),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct SurrealDbConfig {
+    #[serde(default)]
+    pub engine: SurrealEngine,
+    /// Server address for the remote engines, e.g. `localhost:8000`.
+    #[serde(default)]
     pub host: String,
+    /// Data directory for the `surrealkv` engine.
+    #[serde(default = "default_surrealkv_path")]
+    pub path: String,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub password: String,
     pub namespace: String,
     pub database: String,
+    #[serde(default)]
+    pub resilience: ConnectionResilience,
+    #[serde(default)]
+    pub migrations: MigrationSettings,
+}
+
+impl Default for SurrealDbConfig {
+    fn default() -> Self {
+        Self {
+            engine: SurrealEngine::default(),
+            host: String::new(),
+            path: default_surrealkv_path(),
+            username: String::new(),
+            password: String::new(),
+            namespace: String::new(),
+            database: String::new(),
+            resilience: ConnectionResilience::default(),
+            migrations: MigrationSettings::default(),
+        }
+    }
+}
+
+impl SurrealDbConfig {
+    /// Address handed to the SurrealDB client; its scheme selects the engine.
+    pub fn endpoint(&self) -> String {
+        match self.engine {
+            SurrealEngine::Ws => format!("ws://{}", self.host),
+            SurrealEngine::Wss => format!("wss://{}", self.host),
+            SurrealEngine::Http => format!("http://{}", self.host),
+            SurrealEngine::Https => format!("https://{}", self.host),
+            SurrealEngine::Mem => "mem://".to_string(),
+            SurrealEngine::SurrealKv => format!("surrealkv://{}", self.path),
+        }
+    }
+}
+
+/// Where SurrealDB runs: a server reached over WebSocket or HTTP, or an
+/// embedded database inside this process, in memory or on disk.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SurrealEngine {
+    #[default]
+    Ws,
+    Wss,
+    Http,
+    Https,
+    Mem,
+    SurrealKv,
+}
+
+impl SurrealEngine {
+    pub fn is_embedded(self) -> bool {
+        matches!(self, SurrealEngine::Mem | SurrealEngine::SurrealKv)
+    }
+}
+
+fn default_surrealkv_path() -> String {
+    "data/surrealkv".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ConnectionResilience {
+    /// Connection attempts at startup before giving up.
+    pub connect_attempts: u32,
+    /// Delay before the first retry; doubled after each failure.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Bound on reads, and on connecting and signing in.
+    pub query_timeout_ms: u64,
+    /// Bound on writes. A write that times out may still be applied, so
+    /// this is longer than the read timeout.
+    pub write_timeout_ms: u64,
+    /// Consecutive failures that open the circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit fails fast before letting a probe through.
+    pub open_duration_ms: u64,
+}
+
+impl Default for ConnectionResilience {
+    fn default() -> Self {
+        Self {
+            connect_attempts: 10,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            query_timeout_ms: 5_000,
+            write_timeout_ms: 15_000,
+            failure_threshold: 5,
+            open_duration_ms: 10_000,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MigrationSettings {
+    /// Directory holding the `<version>_<name>.surql` scripts.
+    pub directory: String,
+    pub mode: MigrationMode,
+}
+
+impl Default for MigrationSettings {
+    fn default() -> Self {
+        Self {
+            directory: "migrations".to_string(),
+            mode: MigrationMode::default(),
+        }
+    }
+}
+
+/// What startup does about pending migrations: apply them, refuse to start
+/// (`strict`, leaving them to `api migrate`), or ignore them.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationMode {
+    #[default]
+    Apply,
+    Strict,
+    Off,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -24,6 +211,20 @@ pub struct ServerConfig {
     pub address: IpAddr,
     pub allowed_origins: Vec<String>,
     pub allowed_methods: Vec<String>,
+    /// Reject updates and deletes that carry no `If-Match` header.
+    #[serde(default)]
+    pub strict_preconditions: bool,
+    /// Serve `/metrics` on this port only, instead of alongside the API.
+    #[serde(default)]
+    pub admin_port: Option<u16>,
+    /// How long readiness fails after SIGTERM before the server stops taking
+    /// requests, so load balancers can route around it first.
+    #[serde(default = "default_drain_delay_ms")]
+    pub drain_delay_ms: u64,
+}
+
+fn default_drain_delay_ms() -> u64 {
+    5_000
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -31,3 +232,281 @@ pub struct JwtSettings {
     pub secret: String,
     pub expiration: i64,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicySettings {
+    pub min_length: usize,
+    /// Upper bound on password length, which also bounds Argon2 hashing cost.
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Reject passwords containing the username or the email's local part.
+    pub disallow_user_info: bool,
+    /// Minimum strength score from 0 (very weak) to 4 (very strong).
+    pub min_strength: u8,
+    /// File with one known-compromised password per line.
+    pub compromised_passwords_file: Option<String>,
+}
+
+impl Default for PasswordPolicySettings {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            disallow_user_info: true,
+            min_strength: 2,
+            compromised_passwords_file: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Argon2d,
+    Argon2i,
+    Argon2id,
+}
+
+/// Argon2 cost parameters. Stored hashes made with other parameters are
+/// upgraded the next time their owner logs in.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PasswordHashingSettings {
+    pub algorithm: HashAlgorithm,
+    /// Memory cost in KiB.
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// Server-side secret mixed into every hash; never stored in the database.
+    pub pepper: Option<String>,
+    /// Identifier recorded in peppered hashes so a changed pepper is detected.
+    pub pepper_id: String,
+}
+
+impl Default for PasswordHashingSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: HashAlgorithm::Argon2id,
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+            pepper: None,
+            pepper_id: "1".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VersioningSettings {
+    /// Version served on the unversioned `/api` base when `Accept` names none.
+    pub default_version: ApiVersion,
+    pub deprecated: Vec<VersionDeprecation>,
+}
+
+impl Default for VersioningSettings {
+    fn default() -> Self {
+        Self {
+            default_version: ApiVersion::V1,
+            deprecated: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VersionDeprecation {
+    pub version: ApiVersion,
+    /// When the version was deprecated; sent as the `Deprecation` header.
+    pub since: Option<DateTime<Utc>>,
+    /// When the version stops being served; sent as the `Sunset` header.
+    pub sunset: Option<DateTime<Utc>>,
+}
+
+/// Read-through cache for user lookups by id, which every authenticated
+/// request makes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UserCacheSettings {
+    pub enabled: bool,
+    /// How long another instance's change to a user can go unnoticed.
+    pub ttl_ms: u64,
+    pub max_entries: usize,
+}
+
+impl Default for UserCacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_ms: 30_000,
+            max_entries: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccessLogSettings {
+    pub enabled: bool,
+    /// Fraction of successful requests logged, from 0.0 to 1.0. Server
+    /// errors are always logged.
+    pub sample_rate: f64,
+    /// Path prefixes that are never logged, such as health checks.
+    pub exclude_paths: Vec<String>,
+}
+
+impl Default for AccessLogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sample_rate: 1.0,
+            exclude_paths: vec!["/health".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    /// Level applied to every module without a more specific directive.
+    pub level: String,
+    /// Per-module directives in `RUST_LOG` syntax, e.g. `surrealdb=warn`.
+    /// `RUST_LOG` replaces both `level` and `directives` when set.
+    pub directives: Vec<String>,
+    pub output: LogOutput,
+    pub console_format: LogFormat,
+    pub file: LogFileSettings,
+    pub redaction: RedactionSettings,
+    /// Export spans over OTLP when set.
+    pub otlp: Option<OtlpSettings>,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            directives: Vec::new(),
+            output: LogOutput::Both,
+            console_format: LogFormat::Pretty,
+            file: LogFileSettings::default(),
+            redaction: RedactionSettings::default(),
+            otlp: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+    Stdout,
+    File,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Pretty,
+    Compact,
+    Logfmt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    /// Rotate once the file grows past `max_size_mb`.
+    Size,
+    Never,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogFileSettings {
+    pub directory: String,
+    pub file_name: String,
+    pub format: LogFormat,
+    pub rotation: LogRotation,
+    pub max_size_mb: u64,
+    /// Log files kept, the active one included; older files are deleted.
+    /// Unlimited when unset.
+    pub max_files: Option<usize>,
+}
+
+impl Default for LogFileSettings {
+    fn default() -> Self {
+        Self {
+            directory: "logs".to_string(),
+            file_name: "api.log".to_string(),
+            format: LogFormat::Json,
+            rotation: LogRotation::Daily,
+            max_size_mb: 100,
+            max_files: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedactionSettings {
+    pub enabled: bool,
+    pub mode: RedactionMode,
+    /// Sensitive field names. A name also matches fields ending in
+    /// `_<name>` or `.<name>`, so `email` covers `user_email`. Listing `email`
+    /// or `ip` also redacts bare email or IP addresses inside messages.
+    pub fields: Vec<String>,
+    /// Secret mixed into hashes so values cannot be recovered by guessing.
+    pub hash_key: Option<String>,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mode: RedactionMode::Mask,
+            fields: ["email", "password", "token", "authorization"]
+                .map(String::from)
+                .to_vec(),
+            hash_key: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionMode {
+    /// Replace values with `[REDACTED]`.
+    Mask,
+    /// Replace values with a keyed SHA-256 digest, so equal values can still
+    /// be correlated.
+    Hash,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OtlpSettings {
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`.
+    pub endpoint: String,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Fraction of new traces that are sampled; requests joining an upstream
+    /// trace follow its sampling decision.
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+fn default_service_name() -> String {
+    "user-api".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
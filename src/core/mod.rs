@@ -1 +1,2 @@
+pub mod unit_of_work;
 pub mod user;
@@ -0,0 +1,63 @@
+use uuid::Uuid;
+
+use crate::core::user::{
+    dto::{NewUser, VersionPrecondition},
+    model::{PasswordHash, User},
+};
+
+/// A write staged in a [`UnitOfWork`].
+#[derive(Debug, Clone)]
+pub enum Operation {
+    CreateUser(User),
+    DeleteUser {
+        id: String,
+        precondition: VersionPrecondition,
+    },
+}
+
+/// Writes collected in memory and applied together by
+/// [`UserRepository::commit`](crate::core::user::repo::UserRepository::commit)
+/// in one transaction: all of them, or none if any fails.
+#[derive(Debug, Default)]
+pub struct UnitOfWork {
+    operations: Vec<Operation>,
+}
+
+impl UnitOfWork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stages `user` for creation and returns it as it will be stored.
+    pub fn create_user(&mut self, user: NewUser) -> User {
+        let user = User::new(
+            Uuid::new_v4().simple().to_string(),
+            user.username,
+            user.email,
+            PasswordHash::from_hash(user.password),
+            user.roles,
+        );
+
+        self.operations.push(Operation::CreateUser(user.clone()));
+        user
+    }
+
+    /// Stages the deletion of user `id`. The commit fails unless the user
+    /// exists and meets `precondition`.
+    pub fn delete_user(&mut self, id: String, precondition: VersionPrecondition) {
+        self.operations
+            .push(Operation::DeleteUser { id, precondition });
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    pub fn into_operations(self) -> Vec<Operation> {
+        self.operations
+    }
+}
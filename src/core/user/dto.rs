@@ -1,6 +1,10 @@
+use json_patch::{Patch, PatchError};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+use validator::Validate;
 
-use crate::auth::roles::Role;
+use crate::{auth::roles::Role, core::user::model::User};
 
 #[derive(Serialize, Deserialize)]
 pub struct NewUser {
@@ -10,9 +14,11 @@ pub struct NewUser {
     pub roles: Vec<Role>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct UpdateUser {
+    #[validate(length(min = 3, max = 32))]
     pub username: Option<String>,
+    #[validate(email)]
     pub email: Option<String>,
     pub password: Option<String>,
 }
@@ -26,3 +32,63 @@ pub struct ListUsers {
     pub page: Option<u32>,
     pub per_page: Option<u32>,
 }
+
+/// Versions a stored user must be at for a conditional write to apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionPrecondition {
+    Any,
+    OneOf(Vec<u64>),
+}
+
+/// The writable fields of a user, as a patch sees them. Required fields
+/// cannot be removed, and the password is write-only, so it starts absent.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserDocument {
+    pub username: String,
+    pub email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl UserDocument {
+    pub fn of(user: &User) -> Self {
+        Self {
+            username: user.username.clone(),
+            email: user.email.clone(),
+            password: None,
+        }
+    }
+
+    /// The fields `patched` changes, as an update.
+    pub fn changes(self, patched: UserDocument) -> UpdateUser {
+        let changed = |before: String, after: String| (before != after).then_some(after);
+
+        UpdateUser {
+            username: changed(self.username, patched.username),
+            email: changed(self.email, patched.email),
+            password: patched.password,
+        }
+    }
+}
+
+/// Partial update of a user document.
+#[derive(Debug)]
+pub enum UserPatch {
+    /// RFC 7396 JSON Merge Patch.
+    Merge(Value),
+    /// RFC 6902 JSON Patch.
+    Json(Patch),
+}
+
+impl UserPatch {
+    pub fn apply(&self, document: &mut Value) -> Result<(), PatchError> {
+        match self {
+            UserPatch::Merge(patch) => {
+                json_patch::merge(document, patch);
+                Ok(())
+            }
+            UserPatch::Json(patch) => json_patch::patch(document, patch),
+        }
+    }
+}
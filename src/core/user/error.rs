@@ -1,19 +1,38 @@
 use thiserror::Error;
+use validator::ValidationErrors;
 
 #[derive(Debug, Error)]
 pub enum UserServiceError {
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    #[error("Patch test failed: {0}")]
+    PatchTestFailed(String),
+
+    #[error("Invalid fields: {0}")]
+    InvalidFields(ValidationErrors),
+
     #[error("Password hash generation error: {0}")]
     PasswordHashError(String),
 
+    #[error("Current password is incorrect")]
+    IncorrectPassword,
+
     #[error("User not found")]
     UserNotFound,
 
+    #[error("User was modified by another request")]
+    VersionMismatch,
+
+    #[error("Email is already taken")]
+    EmailTaken,
+
     #[error("Repository error: {0}")]
     RepositoryError(String),
 
+    #[error("Database unavailable: {0}")]
+    Unavailable(String),
+
     #[error("Unknown error")]
     Unknown,
 }
@@ -0,0 +1,325 @@
+use std::str::FromStr;
+
+use serde_json::{Map, Value, json};
+use utoipa::ToSchema;
+
+use crate::core::user::model::User;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, rocket::FromFormField, ToSchema)]
+pub enum ExportFormat {
+    #[field(value = "csv")]
+    #[schema(rename = "csv")]
+    Csv,
+    #[field(value = "jsonl")]
+    #[schema(rename = "jsonl")]
+    JsonLines,
+    #[field(value = "json")]
+    #[schema(rename = "json")]
+    Json,
+}
+
+/// Exportable user fields. The password hash is deliberately not one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportColumn {
+    Id,
+    Username,
+    Email,
+    Roles,
+    Version,
+    CreatedAt,
+    UpdatedAt,
+    LastLoginAt,
+    LastLoginIp,
+}
+
+impl ExportColumn {
+    pub const ALL: [ExportColumn; 9] = [
+        ExportColumn::Id,
+        ExportColumn::Username,
+        ExportColumn::Email,
+        ExportColumn::Roles,
+        ExportColumn::Version,
+        ExportColumn::CreatedAt,
+        ExportColumn::UpdatedAt,
+        ExportColumn::LastLoginAt,
+        ExportColumn::LastLoginIp,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportColumn::Id => "id",
+            ExportColumn::Username => "username",
+            ExportColumn::Email => "email",
+            ExportColumn::Roles => "roles",
+            ExportColumn::Version => "version",
+            ExportColumn::CreatedAt => "created_at",
+            ExportColumn::UpdatedAt => "updated_at",
+            ExportColumn::LastLoginAt => "last_login_at",
+            ExportColumn::LastLoginIp => "last_login_ip",
+        }
+    }
+
+    fn value(&self, user: &User) -> Value {
+        match self {
+            ExportColumn::Id => json!(user.id),
+            ExportColumn::Username => json!(user.username),
+            ExportColumn::Email => json!(user.email),
+            ExportColumn::Roles => json!(user.roles),
+            ExportColumn::Version => json!(user.version),
+            ExportColumn::CreatedAt => json!(user.created_at),
+            ExportColumn::UpdatedAt => json!(user.updated_at),
+            ExportColumn::LastLoginAt => json!(user.last_login_at),
+            ExportColumn::LastLoginIp => json!(user.last_login_ip),
+        }
+    }
+
+    /// Renders the column as a CSV cell; roles use the same `;`-separated
+    /// form the importer accepts.
+    fn cell(&self, user: &User) -> String {
+        match self.value(user) {
+            Value::Null => String::new(),
+            Value::String(value) => value,
+            Value::Array(values) => values
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(";"),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl FromStr for ExportColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ExportColumn::ALL
+            .into_iter()
+            .find(|column| column.name() == s)
+            .ok_or_else(|| format!("Unknown export column `{s}`"))
+    }
+}
+
+/// Parses a comma-separated column list, defaulting to every column.
+pub fn parse_columns(columns: Option<&str>) -> Result<Vec<ExportColumn>, String> {
+    match columns {
+        None => Ok(ExportColumn::ALL.to_vec()),
+        Some(columns) => columns
+            .split(',')
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .map(ExportColumn::from_str)
+            .collect(),
+    }
+}
+
+/// Encodes users one record at a time so exports can be streamed.
+pub struct ExportEncoder {
+    format: ExportFormat,
+    columns: Vec<ExportColumn>,
+    written: usize,
+}
+
+impl ExportEncoder {
+    pub fn new(format: ExportFormat, columns: Vec<ExportColumn>) -> Self {
+        Self {
+            format,
+            columns,
+            written: 0,
+        }
+    }
+
+    pub fn header(&self) -> Vec<u8> {
+        match self.format {
+            ExportFormat::Csv => {
+                csv_record(self.columns.iter().map(|column| column.name().to_string()))
+            }
+            ExportFormat::Json => b"[".to_vec(),
+            ExportFormat::JsonLines => Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, user: &User) -> Vec<u8> {
+        let first = self.written == 0;
+        self.written += 1;
+
+        match self.format {
+            ExportFormat::Csv => csv_record(self.columns.iter().map(|column| column.cell(user))),
+            ExportFormat::JsonLines => {
+                let mut line = self.object(user).to_string().into_bytes();
+                line.push(b'\n');
+                line
+            }
+            ExportFormat::Json => {
+                let mut item = if first { Vec::new() } else { b",".to_vec() };
+                item.extend(self.object(user).to_string().into_bytes());
+                item
+            }
+        }
+    }
+
+    pub fn footer(&self) -> Vec<u8> {
+        match self.format {
+            ExportFormat::Json => b"]".to_vec(),
+            ExportFormat::Csv | ExportFormat::JsonLines => Vec::new(),
+        }
+    }
+
+    /// Ends an export that failed partway through, in place of the footer.
+    /// The marker is not a valid record in any format, so a truncated file
+    /// cannot pass for a complete one.
+    pub fn aborted(&self) -> Vec<u8> {
+        let marker = b"#export-aborted\n";
+        match self.format {
+            ExportFormat::Json => [b"\n".as_slice(), marker].concat(),
+            ExportFormat::Csv | ExportFormat::JsonLines => marker.to_vec(),
+        }
+    }
+
+    fn object(&self, user: &User) -> Value {
+        let fields: Map<String, Value> = self
+            .columns
+            .iter()
+            .map(|column| (column.name().to_string(), column.value(user)))
+            .collect();
+
+        Value::Object(fields)
+    }
+}
+
+fn csv_record(cells: impl IntoIterator<Item = String>) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    // Writing into a `Vec` cannot fail.
+    let _ = writer.write_record(cells);
+    writer.into_inner().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::roles::Role, core::user::model::PasswordHash};
+
+    fn user() -> User {
+        User::new(
+            "alice".to_string(),
+            "alice".to_string(),
+            "alice@example.com".to_string(),
+            PasswordHash::from_hash("hash".to_string()),
+            vec![Role::User],
+        )
+    }
+
+    fn csv_rows(body: &[u8]) -> Vec<Vec<String>> {
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(body)
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_string).collect())
+            .collect()
+    }
+
+    fn export(format: ExportFormat, end: fn(&ExportEncoder) -> Vec<u8>) -> Vec<u8> {
+        let mut encoder = ExportEncoder::new(format, ExportColumn::ALL.to_vec());
+        let mut body = encoder.header();
+        body.extend(encoder.record(&user()));
+        body.extend(end(&encoder));
+        body
+    }
+
+    #[test]
+    fn aborted_exports_do_not_parse() {
+        let complete = export(ExportFormat::Json, ExportEncoder::footer);
+        assert!(serde_json::from_slice::<Value>(&complete).is_ok());
+
+        let aborted = export(ExportFormat::Json, ExportEncoder::aborted);
+        assert!(serde_json::from_slice::<Value>(&aborted).is_err());
+
+        let aborted = export(ExportFormat::JsonLines, ExportEncoder::aborted);
+        let last = aborted
+            .split(|b| *b == b'\n')
+            .rfind(|line| !line.is_empty());
+        assert!(serde_json::from_slice::<Value>(last.unwrap()).is_err());
+
+        let aborted = export(ExportFormat::Csv, ExportEncoder::aborted);
+        let records: Result<Vec<_>, _> = csv::Reader::from_reader(aborted.as_slice())
+            .records()
+            .collect();
+        assert!(records.is_err());
+    }
+
+    #[test]
+    fn csv_quotes_cells_that_need_it() {
+        let mut user = user();
+        user.username = "Smith, \"Al\"\nJr.".to_string();
+        user.email = "al=smith@example.com".to_string();
+        user.roles = vec![Role::User, Role::Admin];
+        let columns = vec![
+            ExportColumn::Username,
+            ExportColumn::Email,
+            ExportColumn::Roles,
+        ];
+        let mut encoder = ExportEncoder::new(ExportFormat::Csv, columns);
+
+        let record = encoder.record(&user);
+        assert_eq!(
+            String::from_utf8(record.clone()).unwrap(),
+            "\"Smith, \"\"Al\"\"\nJr.\",al=smith@example.com,User;Admin\n"
+        );
+        assert_eq!(
+            csv_rows(&record),
+            vec![vec![
+                user.username.clone(),
+                user.email.clone(),
+                "User;Admin".to_string(),
+            ]]
+        );
+    }
+
+    #[test]
+    fn csv_leaves_missing_values_empty() {
+        let mut encoder = ExportEncoder::new(
+            ExportFormat::Csv,
+            vec![
+                ExportColumn::Username,
+                ExportColumn::LastLoginAt,
+                ExportColumn::LastLoginIp,
+            ],
+        );
+        assert_eq!(encoder.record(&user()), b"alice,,\n");
+    }
+
+    #[test]
+    fn parses_column_selections() {
+        assert_eq!(parse_columns(None).unwrap(), ExportColumn::ALL.to_vec());
+        assert_eq!(
+            parse_columns(Some(" email, username,")).unwrap(),
+            vec![ExportColumn::Email, ExportColumn::Username]
+        );
+        assert_eq!(
+            parse_columns(Some("email,password")).unwrap_err(),
+            "Unknown export column `password`"
+        );
+    }
+
+    #[test]
+    fn exports_only_the_selected_columns_in_order() {
+        let columns = vec![ExportColumn::Username, ExportColumn::Email];
+
+        let mut csv = ExportEncoder::new(ExportFormat::Csv, columns.clone());
+        let mut body = csv.header();
+        body.extend(csv.record(&user()));
+        assert_eq!(
+            csv_rows(&body),
+            vec![
+                vec!["username", "email"],
+                vec!["alice", "alice@example.com"]
+            ]
+        );
+
+        let mut json = ExportEncoder::new(ExportFormat::JsonLines, columns);
+        let line: Map<String, Value> = serde_json::from_slice(&json.record(&user())).unwrap();
+        let keys: Vec<&str> = line.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["username", "email"]);
+    }
+}
@@ -0,0 +1,203 @@
+use argon2::{
+    Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHasher as _, PasswordVerifier,
+    Version,
+    password_hash::{self, PasswordHash as ParsedHash, SaltString, rand_core::OsRng},
+};
+
+use crate::{
+    config::settings::{HashAlgorithm, PasswordHashingSettings},
+    core::user::model::PasswordHash,
+    metrics::metrics,
+};
+
+/// Outcome of checking a password against a stored hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Invalid,
+    Valid,
+    /// The password matches but the hash was made with other parameters or
+    /// pepper and should be replaced.
+    Outdated,
+}
+
+/// Hashes and verifies passwords with the configured Argon2 parameters.
+#[derive(Debug)]
+pub struct PasswordHasher {
+    algorithm: Algorithm,
+    params: Params,
+    pepper: Option<Vec<u8>>,
+}
+
+impl PasswordHasher {
+    pub fn new(settings: &PasswordHashingSettings) -> Result<Self, argon2::Error> {
+        let mut params = ParamsBuilder::new();
+        params
+            .m_cost(settings.memory_kib)
+            .t_cost(settings.iterations)
+            .p_cost(settings.parallelism);
+
+        if settings.pepper.is_some() {
+            params.keyid(KeyId::new(settings.pepper_id.as_bytes())?);
+        }
+
+        let algorithm = match settings.algorithm {
+            HashAlgorithm::Argon2d => Algorithm::Argon2d,
+            HashAlgorithm::Argon2i => Algorithm::Argon2i,
+            HashAlgorithm::Argon2id => Algorithm::Argon2id,
+        };
+
+        let hasher = Self {
+            algorithm,
+            params: params.build()?,
+            pepper: settings
+                .pepper
+                .as_ref()
+                .map(|pepper| pepper.as_bytes().to_vec()),
+        };
+
+        // Surfaces an oversized pepper at startup rather than on first use.
+        hasher.context(true)?;
+
+        Ok(hasher)
+    }
+
+    fn context(&self, peppered: bool) -> Result<Argon2<'_>, argon2::Error> {
+        match &self.pepper {
+            Some(pepper) if peppered => {
+                Argon2::new_with_secret(pepper, self.algorithm, Version::V0x13, self.params.clone())
+            }
+            _ => Ok(Argon2::new(
+                self.algorithm,
+                Version::V0x13,
+                self.params.clone(),
+            )),
+        }
+    }
+
+    pub fn hash(&self, raw_password: &str) -> Result<PasswordHash, password_hash::Error> {
+        let _timer = metrics()
+            .password_hash_duration
+            .with_label_values(&["hash"])
+            .start_timer();
+        let salt = SaltString::generate(&mut OsRng);
+
+        let hash = self
+            .context(true)?
+            .hash_password(raw_password.as_bytes(), &salt)?
+            .to_string();
+
+        Ok(PasswordHash::from_hash(hash))
+    }
+
+    /// Checks `raw_password` against `hash`. Hashes carrying a key id were
+    /// made with the pepper; hashes without one predate it.
+    pub fn verify(&self, hash: &PasswordHash, raw_password: &str) -> Verification {
+        let Ok(parsed) = ParsedHash::new(hash.as_str()) else {
+            return Verification::Invalid;
+        };
+
+        let Ok(stored) = Params::try_from(&parsed) else {
+            return Verification::Invalid;
+        };
+
+        let _timer = metrics()
+            .password_hash_duration
+            .with_label_values(&["verify"])
+            .start_timer();
+        let peppered = !stored.keyid().is_empty();
+
+        if peppered && (self.pepper.is_none() || stored.keyid() != self.params.keyid()) {
+            return Verification::Invalid;
+        }
+
+        let verified = self.context(peppered).is_ok_and(|context| {
+            context
+                .verify_password(raw_password.as_bytes(), &parsed)
+                .is_ok()
+        });
+
+        if !verified {
+            return Verification::Invalid;
+        }
+
+        let current = Algorithm::try_from(parsed.algorithm).ok() == Some(self.algorithm)
+            && parsed.version == Some(Version::V0x13.into())
+            && stored.m_cost() == self.params.m_cost()
+            && stored.t_cost() == self.params.t_cost()
+            && stored.p_cost() == self.params.p_cost()
+            && stored.keyid() == self.params.keyid();
+
+        if current {
+            Verification::Valid
+        } else {
+            Verification::Outdated
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cheap hasher, peppered with `(key id, pepper)` if given.
+    fn hasher(iterations: u32, pepper: Option<(&str, &str)>) -> PasswordHasher {
+        let (pepper_id, pepper) = match pepper {
+            Some((id, pepper)) => (id, Some(pepper.to_string())),
+            None => ("1", None),
+        };
+
+        PasswordHasher::new(&PasswordHashingSettings {
+            memory_kib: 64,
+            iterations,
+            pepper,
+            pepper_id: pepper_id.to_string(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn verifies_current_hashes() {
+        let hasher = hasher(1, Some(("1", "pepper")));
+        let hash = hasher.hash("secret").unwrap();
+
+        assert_eq!(hasher.verify(&hash, "secret"), Verification::Valid);
+        assert_eq!(hasher.verify(&hash, "Secret"), Verification::Invalid);
+        let garbage = PasswordHash::from_hash("not a hash".to_string());
+        assert_eq!(hasher.verify(&garbage, "secret"), Verification::Invalid);
+    }
+
+    #[test]
+    fn reports_hashes_with_old_parameters_as_outdated() {
+        let old = hasher(1, None).hash("secret").unwrap();
+
+        let current = hasher(2, None);
+        assert_eq!(current.verify(&old, "secret"), Verification::Outdated);
+        assert_eq!(current.verify(&old, "wrong"), Verification::Invalid);
+    }
+
+    #[test]
+    fn reports_hashes_without_the_pepper_as_outdated() {
+        let unpeppered = hasher(1, None).hash("secret").unwrap();
+
+        let peppered = hasher(1, Some(("1", "pepper")));
+        assert_eq!(
+            peppered.verify(&unpeppered, "secret"),
+            Verification::Outdated
+        );
+    }
+
+    #[test]
+    fn rejects_hashes_with_an_unknown_key_id() {
+        let old_pepper = hasher(1, Some(("1", "old pepper"))).hash("secret").unwrap();
+
+        let rotated = hasher(1, Some(("2", "new pepper")));
+        assert_eq!(rotated.verify(&old_pepper, "secret"), Verification::Invalid);
+
+        let unpeppered = hasher(1, None);
+        assert_eq!(
+            unpeppered.verify(&old_pepper, "secret"),
+            Verification::Invalid
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{
+    Deserialize, Serialize,
+    de::{IntoDeserializer, value::Error as ValueError},
+};
+use tracing::{error, info};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    auth::roles::Role,
+    core::user::{error::UserServiceError, service::UserService},
+};
+
+/// How long finished jobs stay available for polling.
+const JOB_RETENTION_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, rocket::FromFormField, ToSchema)]
+pub enum ImportFormat {
+    #[field(value = "csv")]
+    #[schema(rename = "csv")]
+    Csv,
+    #[field(value = "jsonl")]
+    #[schema(rename = "jsonl")]
+    JsonLines,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct ImportRow {
+    #[validate(length(min = 3, max = 32))]
+    pub username: String,
+    #[validate(email)]
+    pub email: String,
+    pub password: String,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+}
+
+/// A parsed row together with the line it came from.
+#[derive(Debug, Clone)]
+pub struct NumberedRow {
+    pub line: usize,
+    pub row: ImportRow,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RowError {
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub message: String,
+}
+
+impl RowError {
+    pub fn new(line: usize, email: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            email: email.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    username: String,
+    email: String,
+    password: String,
+    #[serde(default)]
+    roles: Option<String>,
+}
+
+impl CsvRow {
+    fn into_row(self) -> Result<ImportRow, String> {
+        let roles = self
+            .roles
+            .as_deref()
+            .unwrap_or_default()
+            .split(';')
+            .map(str::trim)
+            .filter(|role| !role.is_empty())
+            .map(|role| {
+                Role::deserialize(role.into_deserializer())
+                    .map_err(|e: ValueError| format!("Invalid role `{role}`: {e}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ImportRow {
+            username: self.username,
+            email: self.email,
+            password: self.password,
+            roles,
+        })
+    }
+}
+
+/// Splits an upload into rows, collecting per-line parse errors instead of
+/// aborting on the first one.
+pub fn parse(format: ImportFormat, body: &str) -> (Vec<NumberedRow>, Vec<RowError>) {
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+
+    match format {
+        ImportFormat::JsonLines => {
+            for (index, line) in body.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match serde_json::from_str::<ImportRow>(line) {
+                    Ok(row) => rows.push(NumberedRow {
+                        line: index + 1,
+                        row,
+                    }),
+                    Err(e) => errors.push(RowError::new(index + 1, None, e.to_string())),
+                }
+            }
+        }
+        ImportFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(body.as_bytes());
+
+            let headers = match reader.headers() {
+                Ok(headers) => headers.clone(),
+                Err(e) => {
+                    errors.push(RowError::new(1, None, e.to_string()));
+                    return (rows, errors);
+                }
+            };
+
+            for record in reader.records() {
+                let row = record.map_err(|e| {
+                    let line = e.position().map(|p| p.line() as usize).unwrap_or_default();
+                    RowError::new(line, None, e.to_string())
+                });
+
+                let row = row.and_then(|record| {
+                    let line = record
+                        .position()
+                        .map(|p| p.line() as usize)
+                        .unwrap_or_default();
+
+                    record
+                        .deserialize::<CsvRow>(Some(&headers))
+                        .map_err(|e| e.to_string())
+                        .and_then(CsvRow::into_row)
+                        .map(|row| NumberedRow { line, row })
+                        .map_err(|message| RowError::new(line, None, message))
+                });
+
+                match row {
+                    Ok(row) => rows.push(row),
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+    }
+
+    (rows, errors)
+}
+
+/// Flags rows whose email or username already appeared earlier in the same upload.
+/// Emails compare exactly, as the unique index and `get_by_email` do.
+pub fn find_duplicates(rows: Vec<NumberedRow>) -> (Vec<NumberedRow>, Vec<RowError>) {
+    let mut emails = HashSet::new();
+    let mut usernames = HashSet::new();
+    let mut unique = Vec::new();
+    let mut errors = Vec::new();
+
+    for numbered in rows {
+        let row = &numbered.row;
+
+        if !emails.insert(row.email.clone()) {
+            errors.push(RowError::new(
+                numbered.line,
+                Some(&row.email),
+                "Duplicate email in import",
+            ));
+        } else if !usernames.insert(row.username.clone()) {
+            errors.push(RowError::new(
+                numbered.line,
+                Some(&row.email),
+                "Duplicate username in import",
+            ));
+        } else {
+            unique.push(numbered);
+        }
+    }
+
+    (unique, errors)
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub total: usize,
+    pub valid: usize,
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportState {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportJob {
+    pub id: String,
+    pub state: ImportState,
+    pub total: usize,
+    pub processed: usize,
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// In-process registry of import jobs, polled through the import status route.
+#[derive(Default)]
+pub struct ImportJobs {
+    jobs: RwLock<HashMap<String, ImportJob>>,
+}
+
+impl ImportJobs {
+    pub fn create(&self, total: usize, errors: Vec<RowError>) -> ImportJob {
+        let job = ImportJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            state: ImportState::Pending,
+            total,
+            processed: errors.len(),
+            imported: 0,
+            errors,
+            created_at: Utc::now(),
+            finished_at: None,
+        };
+
+        let mut jobs = self.jobs.write().unwrap_or_else(|e| e.into_inner());
+        let cutoff = Utc::now() - Duration::hours(JOB_RETENTION_HOURS);
+        jobs.retain(|_, job| job.finished_at.is_none_or(|finished| finished > cutoff));
+        jobs.insert(job.id.clone(), job.clone());
+
+        job
+    }
+
+    pub fn get(&self, id: &str) -> Option<ImportJob> {
+        let jobs = self.jobs.read().unwrap_or_else(|e| e.into_inner());
+        jobs.get(id).cloned()
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut ImportJob)) {
+        let mut jobs = self.jobs.write().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(job) = jobs.get_mut(id) {
+            f(job);
+        }
+    }
+}
+
+/// Drives an import job to completion: rows that pass the existence check
+/// are inserted in chunks, each chunk in its own transaction.
+pub async fn run_import(
+    service: Arc<UserService>,
+    jobs: Arc<ImportJobs>,
+    job_id: String,
+    rows: Vec<NumberedRow>,
+    chunk_size: usize,
+) {
+    jobs.update(&job_id, |job| job.state = ImportState::Running);
+
+    let (rows, errors) = service.check_import(rows).await;
+
+    jobs.update(&job_id, |job| {
+        job.processed += errors.len();
+        job.errors.extend(errors);
+    });
+
+    for chunk in rows.chunks(chunk_size.max(1)) {
+        let lines: Vec<usize> = chunk.iter().map(|numbered| numbered.line).collect();
+
+        match service.import_chunk(chunk.to_vec()).await {
+            Ok(imported) => jobs.update(&job_id, |job| {
+                job.processed += chunk.len();
+                job.imported += imported;
+            }),
+            // EmailTaken: an email was registered after check_import let it through.
+            Err(e @ (UserServiceError::RepositoryError(_) | UserServiceError::EmailTaken)) => {
+                error!(job_id = %job_id, "Import chunk rolled back: {e}");
+
+                jobs.update(&job_id, |job| {
+                    job.processed += chunk.len();
+                    job.errors.extend(
+                        lines.iter().map(|line| {
+                            RowError::new(*line, None, format!("Chunk rolled back: {e}"))
+                        }),
+                    );
+                });
+            }
+            Err(e) => {
+                error!(job_id = %job_id, "Import aborted: {:?}", e);
+
+                jobs.update(&job_id, |job| {
+                    job.state = ImportState::Failed;
+                    job.finished_at = Some(Utc::now());
+                });
+                return;
+            }
+        }
+    }
+
+    jobs.update(&job_id, |job| {
+        info!(job_id = %job.id, imported = job.imported, "Import finished");
+        job.state = ImportState::Completed;
+        job.finished_at = Some(Utc::now());
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::{
+        config::settings::{PasswordHashingSettings, PasswordPolicySettings},
+        core::user::{
+            dto::NewUser, hashing::PasswordHasher, password_policy::PasswordPolicy,
+            repo::UserRepository,
+        },
+        infra::db::{hooked::HookedUserRepository, memory_repo::InMemoryUserRepository},
+    };
+
+    fn row(line: usize, username: &str, email: &str) -> NumberedRow {
+        NumberedRow {
+            line,
+            row: ImportRow {
+                username: username.to_string(),
+                email: email.to_string(),
+                password: "password".to_string(),
+                roles: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn duplicate_emails_match_exactly() {
+        let (unique, errors) = find_duplicates(vec![
+            row(1, "alice", "alice@example.com"),
+            row(2, "alice2", "Alice@example.com"),
+            row(3, "alice3", "alice@example.com"),
+        ]);
+
+        assert_eq!(unique.iter().map(|r| r.line).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+    }
+
+    #[tokio::test]
+    async fn taken_email_rejects_its_chunk_only() {
+        // Another request registers the email right after the import's check.
+        let users: Arc<dyn UserRepository + Send + Sync> = Arc::new(InMemoryUserRepository::new());
+        let registered = AtomicBool::new(false);
+        let repo = HookedUserRepository::new(Arc::clone(&users), move |method| {
+            let racer = Arc::clone(&users);
+            let first = method == "get_by_email" && !registered.swap(true, Ordering::SeqCst);
+            async move {
+                if first {
+                    racer
+                        .create(NewUser {
+                            username: "taken".to_string(),
+                            email: "taken@example.com".to_string(),
+                            password: "hash".to_string(),
+                            roles: Vec::new(),
+                        })
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        let policy = PasswordPolicy::new(PasswordPolicySettings {
+            min_strength: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        let hasher = PasswordHasher::new(&PasswordHashingSettings {
+            memory_kib: 64,
+            iterations: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        let service = Arc::new(UserService::new(Arc::new(repo), policy, hasher));
+        let jobs = Arc::new(ImportJobs::default());
+        let job = jobs.create(2, Vec::new());
+
+        let rows = vec![
+            row(1, "late", "taken@example.com"),
+            row(2, "fresh", "fresh@example.com"),
+        ];
+        run_import(service, Arc::clone(&jobs), job.id.clone(), rows, 1).await;
+
+        let job = jobs.get(&job.id).unwrap();
+        assert_eq!(job.state, ImportState::Completed);
+        assert_eq!((job.processed, job.imported), (2, 1));
+        assert_eq!(job.errors.len(), 1);
+        assert_eq!(job.errors[0].line, 1);
+    }
+}
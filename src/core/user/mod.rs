@@ -1,5 +1,9 @@
 pub mod dto;
 pub mod error;
+pub mod export;
+pub mod hashing;
+pub mod import;
 pub mod model;
+pub mod password_policy;
 pub mod repo;
 pub mod service;
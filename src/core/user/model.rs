@@ -1,8 +1,6 @@
-use argon2::{
-    Argon2, PasswordVerifier,
-    password_hash::{PasswordHash as PH, PasswordHasher, SaltString, rand_core::OsRng},
-};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use surrealdb::sql::Thing;
 
 use crate::auth::roles::Role;
@@ -15,6 +13,14 @@ pub struct User {
     pub email: String,
     pub password: PasswordHash,
     pub roles: Vec<Role>,
+    #[serde(default)]
+    pub version: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_login_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_ip")]
+    pub last_login_ip: Option<IpAddr>,
 }
 
 impl User {
@@ -25,16 +31,24 @@ impl User {
         password: PasswordHash,
         roles: Vec<Role>,
     ) -> Self {
+        let now = Utc::now();
+
         Self {
             id,
             username,
             email,
             password,
             roles,
+            version: 1,
+            created_at: now,
+            updated_at: now,
+            last_login_at: None,
+            last_login_ip: None,
         }
     }
 }
 
+/// An encoded Argon2 hash; see [`PasswordHasher`](super::hashing::PasswordHasher).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PasswordHash(String);
 
@@ -43,30 +57,6 @@ impl PasswordHash {
         Self(hash)
     }
 
-    pub fn raw(raw: String) -> Result<Self, argon2::password_hash::Error> {
-        PasswordHash::hash(&raw)
-    }
-
-    fn hash(raw_password: &str) -> Result<Self, argon2::password_hash::Error> {
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-
-        let password_hash = argon2
-            .hash_password(raw_password.as_bytes(), &salt)?
-            .to_string();
-
-        Ok(PasswordHash(password_hash))
-    }
-
-    pub fn verify(&self, raw_password: &str) -> bool {
-        match PH::new(&self.0) {
-            Ok(parsed_hash) => Argon2::default()
-                .verify_password(raw_password.as_bytes(), &parsed_hash)
-                .is_ok(),
-            Err(_) => false,
-        }
-    }
-
     pub fn as_str(&self) -> &str {
         &self.0
     }
@@ -102,3 +92,14 @@ where
         Err(serde::de::Error::custom("Expected string ID"))
     }
 }
+
+/// Addresses are stored as text, which SurrealDB's deserializer does not
+/// turn into an `IpAddr` by itself.
+fn deserialize_ip<'de, D>(deserializer: D) -> Result<Option<IpAddr>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|ip| ip.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
@@ -0,0 +1,393 @@
+use std::{borrow::Cow, collections::HashSet, fs, io};
+
+use validator::{ValidationError, ValidationErrors};
+
+use crate::config::settings::PasswordPolicySettings;
+
+/// Rules a new password must satisfy, built from [`PasswordPolicySettings`].
+#[derive(Debug, Default)]
+pub struct PasswordPolicy {
+    settings: PasswordPolicySettings,
+    compromised: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn new(settings: PasswordPolicySettings) -> io::Result<Self> {
+        let compromised = match &settings.compromised_passwords_file {
+            Some(path) => fs::read_to_string(path)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        Ok(Self {
+            settings,
+            compromised,
+        })
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.settings.max_length
+    }
+
+    /// Checks `password` for the user identified by `username` and `email`,
+    /// reporting every violated rule under the `password` field.
+    pub fn check(
+        &self,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> Result<(), ValidationErrors> {
+        let mut violations = self.violations(password);
+
+        if self.settings.disallow_user_info && contains_user_info(password, username, email) {
+            violations.push(violation(
+                "contains_user_info",
+                "Password must not contain the username or email",
+            ));
+        }
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let mut errors = ValidationErrors::new();
+
+        for error in violations {
+            errors.add("password", error);
+        }
+
+        Err(errors)
+    }
+
+    /// The rules `password` breaks, apart from those that depend on whom it
+    /// belongs to.
+    fn violations(&self, password: &str) -> Vec<ValidationError> {
+        let settings = &self.settings;
+        let length = password.chars().count();
+        let mut violations = Vec::new();
+
+        if length < settings.min_length {
+            violations.push(violation(
+                "too_short",
+                format!(
+                    "Password must have at least {} characters",
+                    settings.min_length
+                ),
+            ));
+        }
+
+        if length > settings.max_length {
+            violations.push(violation(
+                "too_long",
+                format!(
+                    "Password must have at most {} characters",
+                    settings.max_length
+                ),
+            ));
+        }
+
+        let classes = CharClasses::of(password);
+        let required = [
+            (
+                settings.require_lowercase,
+                classes.lowercase,
+                "missing_lowercase",
+                "a lowercase letter",
+            ),
+            (
+                settings.require_uppercase,
+                classes.uppercase,
+                "missing_uppercase",
+                "an uppercase letter",
+            ),
+            (
+                settings.require_digit,
+                classes.digit,
+                "missing_digit",
+                "a digit",
+            ),
+            (
+                settings.require_symbol,
+                classes.symbol,
+                "missing_symbol",
+                "a symbol",
+            ),
+        ];
+
+        for (required, present, code, name) in required {
+            if required && !present {
+                violations.push(violation(code, format!("Password must contain {name}")));
+            }
+        }
+
+        if self.compromised.contains(&password.to_lowercase()) {
+            violations.push(violation(
+                "compromised",
+                "Password appears in a list of compromised passwords",
+            ));
+        }
+
+        if strength(password) < settings.min_strength {
+            violations.push(violation("too_weak", "Password is too easy to guess"));
+        }
+
+        violations
+    }
+}
+
+/// `#[validate(custom(function = "password_policy::validate", use_context))]`
+/// rule for request fields holding a new password. Reports the first rule
+/// `password` breaks; the user service checks the rules that depend on the
+/// username and email.
+pub fn validate(password: &str, policy: &PasswordPolicy) -> Result<(), ValidationError> {
+    match policy.violations(password).into_iter().next() {
+        Some(violation) => Err(violation),
+        None => Ok(()),
+    }
+}
+
+fn violation(code: &'static str, message: impl Into<Cow<'static, str>>) -> ValidationError {
+    ValidationError::new(code).with_message(message.into())
+}
+
+fn contains_user_info(password: &str, username: &str, email: &str) -> bool {
+    let password = password.to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default();
+
+    [username, local_part]
+        .into_iter()
+        .map(str::to_lowercase)
+        .filter(|info| info.chars().count() >= 3)
+        .any(|info| password.contains(&info))
+}
+
+#[derive(Debug, Default)]
+struct CharClasses {
+    lowercase: bool,
+    uppercase: bool,
+    digit: bool,
+    symbol: bool,
+    other: bool,
+}
+
+impl CharClasses {
+    fn of(password: &str) -> Self {
+        let mut classes = CharClasses::default();
+
+        for c in password.chars() {
+            match c {
+                'a'..='z' => classes.lowercase = true,
+                'A'..='Z' => classes.uppercase = true,
+                '0'..='9' => classes.digit = true,
+                c if c.is_ascii_punctuation() || c == ' ' => classes.symbol = true,
+                _ => classes.other = true,
+            }
+        }
+
+        classes
+    }
+
+    fn pool_size(&self) -> u32 {
+        [
+            (self.lowercase, 26),
+            (self.uppercase, 26),
+            (self.digit, 10),
+            (self.symbol, 33),
+            (self.other, 100),
+        ]
+        .into_iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, size)| size)
+        .sum()
+    }
+}
+
+/// Estimates password strength from its brute-force entropy, scored from
+/// 0 (very weak) to 4 (very strong).
+pub fn strength(password: &str) -> u8 {
+    score(entropy(password))
+}
+
+/// Bits of brute-force entropy. Repeated characters only count once, so
+/// `aaaaaaaaaaaa` does not pass for a long password.
+fn entropy(password: &str) -> f64 {
+    let pool = CharClasses::of(password).pool_size();
+
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let distinct = password.chars().collect::<HashSet<_>>().len() as f64;
+    let length = password.chars().count() as f64;
+
+    (distinct + length) / 2.0 * f64::from(pool).log2()
+}
+
+fn score(entropy: f64) -> u8 {
+    match entropy {
+        e if e < 28.0 => 0,
+        e if e < 36.0 => 1,
+        e if e < 60.0 => 2,
+        e if e < 128.0 => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf};
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn policy(settings: PasswordPolicySettings) -> PasswordPolicy {
+        PasswordPolicy::new(PasswordPolicySettings {
+            min_strength: 0,
+            disallow_user_info: false,
+            ..settings
+        })
+        .unwrap()
+    }
+
+    fn codes(policy: &PasswordPolicy, password: &str) -> Vec<String> {
+        match policy.check(password, "ana", "ana@example.com") {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.field_errors()["password"]
+                .iter()
+                .map(|error| error.code.to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn bounds_the_length() {
+        let policy = policy(PasswordPolicySettings {
+            min_length: 8,
+            max_length: 10,
+            ..Default::default()
+        });
+
+        assert_eq!(codes(&policy, "abcdefg"), ["too_short"]);
+        assert!(codes(&policy, "abcdefgh").is_empty());
+        assert!(codes(&policy, "abcdefghij").is_empty());
+        assert_eq!(codes(&policy, "abcdefghijk"), ["too_long"]);
+        // Characters, not bytes.
+        assert!(codes(&policy, "äöüäöüäö").is_empty());
+    }
+
+    #[test]
+    fn requires_each_enabled_character_class() {
+        let policy = policy(PasswordPolicySettings {
+            min_length: 1,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            codes(&policy, "éé"),
+            [
+                "missing_lowercase",
+                "missing_uppercase",
+                "missing_digit",
+                "missing_symbol"
+            ]
+        );
+        assert_eq!(codes(&policy, "aB3"), ["missing_symbol"]);
+        assert_eq!(codes(&policy, "aB !"), ["missing_digit"]);
+        assert!(codes(&policy, "aB3!").is_empty());
+
+        let lenient = self::policy(PasswordPolicySettings {
+            min_length: 1,
+            ..Default::default()
+        });
+        assert!(codes(&lenient, "éé").is_empty());
+    }
+
+    #[test]
+    fn rejects_compromised_passwords_in_any_case() {
+        let path: PathBuf = env::temp_dir().join(format!("compromised-{}", Uuid::new_v4()));
+        fs::write(&path, "# known leaks\n\n  Hunter2  \nletmein\n").unwrap();
+
+        let policy = policy(PasswordPolicySettings {
+            min_length: 1,
+            compromised_passwords_file: Some(path.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(codes(&policy, "hunter2"), ["compromised"]);
+        assert_eq!(codes(&policy, "LetMeIn"), ["compromised"]);
+        assert!(codes(&policy, "# known leaks").is_empty());
+        assert!(codes(&policy, "hunter3").is_empty());
+
+        let missing = PasswordPolicy::new(PasswordPolicySettings {
+            compromised_passwords_file: Some(path.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn rejects_user_info_only_when_enabled() {
+        let strict = PasswordPolicy::new(PasswordPolicySettings {
+            min_strength: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        let check = |password| strict.check(password, "Anabel", "ana.b@example.com");
+
+        assert!(check("xxANABELxx").is_err());
+        assert!(check("xxana.bxx").is_err());
+        assert!(check("correct horse").is_ok());
+
+        let lenient = policy(PasswordPolicySettings::default());
+        assert!(codes(&lenient, "xxana.bxx").is_empty());
+    }
+
+    #[test]
+    fn scores_change_exactly_at_the_thresholds() {
+        let steps = [(28.0, 1), (36.0, 2), (60.0, 3), (128.0, 4)];
+
+        for (threshold, score_at) in steps {
+            assert_eq!(score(threshold), score_at, "at {threshold}");
+            assert_eq!(score(threshold - 1e-9), score_at - 1, "below {threshold}");
+        }
+        assert_eq!(score(0.0), 0);
+    }
+
+    #[test]
+    fn measures_entropy_over_distinct_characters() {
+        assert_eq!(entropy(""), 0.0);
+        // Eight digits: (8 + 8) / 2 * log2(10).
+        assert!((entropy("01234567") - 8.0 * 10f64.log2()).abs() < 1e-9);
+        // Repeats count once: (1 + 12) / 2 * log2(26).
+        assert!((entropy("aaaaaaaaaaaa") - 6.5 * 26f64.log2()).abs() < 1e-9);
+
+        assert_eq!(strength("aaaaaaaaaaaa"), 1);
+        assert_eq!(strength("01234567"), 0);
+        assert_eq!(strength("correct horse battery staple"), 3);
+        assert_eq!(strength("correct horse battery staple, Tr0ub4dor&3!"), 4);
+    }
+
+    #[test]
+    fn enforces_the_minimum_strength() {
+        let policy = PasswordPolicy::new(PasswordPolicySettings {
+            min_length: 1,
+            min_strength: 2,
+            disallow_user_info: false,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(codes(&policy, "aaaaaaaaaaaa"), ["too_weak"]);
+        assert!(codes(&policy, "Tr0ub4dor&3").is_empty());
+    }
+}
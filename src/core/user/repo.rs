@@ -1,11 +1,14 @@
+use std::net::IpAddr;
+
 use rocket::async_trait;
 use thiserror::Error;
 
 use crate::{
     api::requests::PageConfig,
+    core::unit_of_work::UnitOfWork,
     core::user::{
-        dto::{NewUser, UpdateUser},
-        model::User,
+        dto::{NewUser, UpdateUser, VersionPrecondition},
+        model::{PasswordHash, User},
     },
 };
 
@@ -17,19 +20,53 @@ pub enum UserRepositoryError {
     #[error("Database connection error")]
     DatabaseError(String),
 
+    #[error("Database unavailable: {0}")]
+    Unavailable(String),
+
     #[error("Unknown error")]
     Unknown,
 
     #[error("Query failed: {0}")]
     QueryFailed(String),
+
+    #[error("User version does not match")]
+    VersionMismatch,
+
+    #[error("Email is already taken")]
+    EmailTaken,
 }
 
 #[async_trait]
 pub trait UserRepository: Send + Sync {
-    async fn get_by_id(&self, id: String) -> Option<User>;
-    async fn get_by_email(&self, username: &str) -> Option<User>;
+    async fn get_by_id(&self, id: String) -> Result<Option<User>, UserRepositoryError>;
+    async fn get_by_email(&self, username: &str) -> Result<Option<User>, UserRepositoryError>;
     async fn create(&self, user: NewUser) -> Result<User, UserRepositoryError>;
-    async fn update(&self, id: String, data: UpdateUser) -> Result<User, UserRepositoryError>;
-    async fn delete(&self, id: String) -> Result<(), UserRepositoryError>;
+    /// Inserts all users or none of them.
+    async fn create_many(&self, users: Vec<NewUser>) -> Result<Vec<User>, UserRepositoryError>;
+    /// Applies `data`; a password in it must already be hashed.
+    async fn update(
+        &self,
+        id: String,
+        data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserRepositoryError>;
+    async fn delete(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserRepositoryError>;
+    async fn record_login(&self, id: String, ip: Option<IpAddr>)
+    -> Result<(), UserRepositoryError>;
+    /// Swaps the stored hash for `new` if it still equals `current`, leaving
+    /// the version and timestamps untouched.
+    async fn replace_password_hash(
+        &self,
+        id: String,
+        current: PasswordHash,
+        new: PasswordHash,
+    ) -> Result<(), UserRepositoryError>;
     async fn list(&self, spec: PageConfig) -> Result<Vec<User>, UserRepositoryError>;
+    /// Applies every operation staged in `work` in one transaction, rolling
+    /// all of them back if any fails.
+    async fn commit(&self, work: UnitOfWork) -> Result<(), UserRepositoryError>;
 }
@@ -1,23 +1,81 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
+
+use json_patch::PatchErrorKind;
+use tokio::task::{self, JoinSet};
+use tracing::{info, warn};
+use validator::Validate;
 
 use crate::{
     api::requests::PageConfig,
     auth::roles::Role,
+    core::unit_of_work::UnitOfWork,
     core::user::{
-        dto::{NewUser, UpdateUser},
+        dto::{NewUser, UpdateUser, UserDocument, UserPatch, VersionPrecondition},
         error::UserServiceError,
+        hashing::{PasswordHasher, Verification},
+        import::{NumberedRow, RowError, find_duplicates},
         model::{PasswordHash, User},
+        password_policy::PasswordPolicy,
         repo::{UserRepository, UserRepositoryError},
     },
 };
 
 pub struct UserService {
     repo: Arc<dyn UserRepository + Send + Sync>,
+    password_policy: PasswordPolicy,
+    hasher: Arc<PasswordHasher>,
 }
 
 impl UserService {
-    pub fn new(repo: Arc<dyn UserRepository + Send + Sync>) -> Self {
-        Self { repo }
+    pub fn new(
+        repo: Arc<dyn UserRepository + Send + Sync>,
+        password_policy: PasswordPolicy,
+        hasher: PasswordHasher,
+    ) -> Self {
+        Self {
+            repo,
+            password_policy,
+            hasher: Arc::new(hasher),
+        }
+    }
+
+    /// The policy new passwords are held to.
+    pub fn password_policy(&self) -> &PasswordPolicy {
+        &self.password_policy
+    }
+
+    fn check_password(
+        &self,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> Result<(), UserServiceError> {
+        self.password_policy
+            .check(password, username, email)
+            .map_err(UserServiceError::InvalidFields)
+    }
+
+    /// Hashes on the blocking pool, so Argon2 never stalls an async worker.
+    async fn hash(&self, raw_password: String) -> Result<PasswordHash, UserServiceError> {
+        let hasher = Arc::clone(&self.hasher);
+
+        task::spawn_blocking(move || hasher.hash(&raw_password))
+            .await
+            .map_err(|e| UserServiceError::PasswordHashError(e.to_string()))?
+            .map_err(|e| UserServiceError::PasswordHashError(e.to_string()))
+    }
+
+    /// Verifies on the blocking pool, like [`Self::hash`].
+    async fn verify(
+        &self,
+        hash: PasswordHash,
+        raw_password: String,
+    ) -> Result<Verification, UserServiceError> {
+        let hasher = Arc::clone(&self.hasher);
+
+        task::spawn_blocking(move || hasher.verify(&hash, &raw_password))
+            .await
+            .map_err(|e| UserServiceError::PasswordHashError(e.to_string()))
     }
 
     pub async fn create_user(
@@ -27,36 +85,273 @@ impl UserService {
         raw_password: String,
         roles: Vec<Role>,
     ) -> Result<User, UserServiceError> {
-        let password_hash = PasswordHash::raw(raw_password)
-            .map_err(|e| UserServiceError::PasswordHashError(e.to_string()))?;
+        self.check_password(&raw_password, &username, &email)?;
+
+        let password_hash = self.hash(raw_password).await?;
 
-        let new_user = NewUser {
+        let mut work = UnitOfWork::new();
+        let user = work.create_user(NewUser {
             username,
             email,
             password: password_hash.as_str().to_string(),
             roles,
-        };
-
-        let user = self.repo.create(new_user).await?;
+        });
+        self.repo.commit(work).await?;
 
         Ok(user)
     }
 
+    /// Validates import rows and drops those that would clash with each other
+    /// or with users that already exist.
+    pub async fn check_import(&self, rows: Vec<NumberedRow>) -> (Vec<NumberedRow>, Vec<RowError>) {
+        let mut errors = Vec::new();
+
+        let rows = rows
+            .into_iter()
+            .filter(|numbered| {
+                let row = &numbered.row;
+                let result = row.validate().and_then(|_| {
+                    self.password_policy
+                        .check(&row.password, &row.username, &row.email)
+                });
+
+                if let Err(e) = &result {
+                    errors.push(RowError::new(
+                        numbered.line,
+                        Some(&row.email),
+                        e.to_string(),
+                    ));
+                }
+
+                result.is_ok()
+            })
+            .collect();
+
+        let (rows, duplicates) = find_duplicates(rows);
+        errors.extend(duplicates);
+
+        let mut valid = Vec::with_capacity(rows.len());
+
+        for numbered in rows {
+            match self.repo.get_by_email(&numbered.row.email).await {
+                Ok(None) => valid.push(numbered),
+                Ok(Some(_)) => errors.push(RowError::new(
+                    numbered.line,
+                    Some(&numbered.row.email),
+                    "User already exists",
+                )),
+                Err(e) => errors.push(RowError::new(
+                    numbered.line,
+                    Some(&numbered.row.email),
+                    format!("Could not check for an existing user: {e}"),
+                )),
+            }
+        }
+
+        errors.sort_by_key(|e| e.line);
+        (valid, errors)
+    }
+
+    /// Hashes a chunk of import rows in parallel and inserts them atomically.
+    pub async fn import_chunk(&self, rows: Vec<NumberedRow>) -> Result<usize, UserServiceError> {
+        let mut hashing = JoinSet::new();
+
+        for (index, numbered) in rows.into_iter().enumerate() {
+            let hasher = Arc::clone(&self.hasher);
+
+            hashing.spawn_blocking(move || {
+                let row = numbered.row;
+                hasher.hash(&row.password).map(|hash| {
+                    let user = NewUser {
+                        username: row.username,
+                        email: row.email,
+                        password: hash.as_str().to_string(),
+                        roles: row.roles,
+                    };
+                    (index, user)
+                })
+            });
+        }
+
+        let mut users = Vec::with_capacity(hashing.len());
+
+        while let Some(result) = hashing.join_next().await {
+            let hashed = result.map_err(|e| UserServiceError::PasswordHashError(e.to_string()))?;
+            users.push(hashed.map_err(|e| UserServiceError::PasswordHashError(e.to_string()))?);
+        }
+
+        users.sort_by_key(|(index, _)| *index);
+
+        let created = self
+            .repo
+            .create_many(users.into_iter().map(|(_, user)| user).collect())
+            .await?;
+
+        Ok(created.len())
+    }
+
     pub async fn list_users(&self, spec: PageConfig) -> Result<Vec<User>, UserServiceError> {
         let users = self.repo.list(spec).await?;
         Ok(users)
     }
 
-    pub async fn delete_user(&self, id: String) -> Result<(), UserServiceError> {
-        self.repo.delete(id).await.map_err(|e| e.into())
+    pub async fn delete_user(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserServiceError> {
+        self.repo
+            .delete(id, precondition)
+            .await
+            .map_err(|e| e.into())
     }
 
     pub async fn update_user(
         &self,
         id: String,
         data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserServiceError> {
+        data.validate().map_err(UserServiceError::InvalidFields)?;
+        self.check_changed_password(&id, &data).await?;
+        let data = self.hash_changes(data).await?;
+
+        self.repo
+            .update(id, data, precondition)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    /// Applies `patch` to the user's document and persists the writable
+    /// fields it changed. The write is conditional on the version the patch
+    /// was applied to, so concurrent edits surface as a version mismatch.
+    pub async fn patch_user(
+        &self,
+        id: String,
+        patch: UserPatch,
+        precondition: VersionPrecondition,
     ) -> Result<User, UserServiceError> {
-        self.repo.update(id, data).await.map_err(|e| e.into())
+        let user = self
+            .repo
+            .get_by_id(id.clone())
+            .await?
+            .ok_or(UserServiceError::UserNotFound)?;
+
+        if let VersionPrecondition::OneOf(versions) = &precondition
+            && !versions.contains(&user.version)
+        {
+            return Err(UserServiceError::VersionMismatch);
+        }
+
+        let original =
+            serde_json::to_value(UserDocument::of(&user)).map_err(|_| UserServiceError::Unknown)?;
+        let mut document = original.clone();
+
+        patch.apply(&mut document).map_err(|e| match e.kind {
+            PatchErrorKind::TestFailed => UserServiceError::PatchTestFailed(e.to_string()),
+            _ => UserServiceError::ValidationError(e.to_string()),
+        })?;
+
+        let patched: UserDocument = serde_json::from_value(document)
+            .map_err(|e| UserServiceError::ValidationError(format!("Invalid user: {e}")))?;
+
+        // Nothing to write, so the version and ETag stay as they are.
+        if serde_json::to_value(&patched).is_ok_and(|patched| patched == original) {
+            return Ok(user);
+        }
+        let changes = UserDocument::of(&user).changes(patched);
+        changes
+            .validate()
+            .map_err(UserServiceError::InvalidFields)?;
+
+        if let Some(password) = &changes.password {
+            let username = changes.username.as_deref().unwrap_or(&user.username);
+            let email = changes.email.as_deref().unwrap_or(&user.email);
+            self.check_password(password, username, email)?;
+        }
+
+        let changes = self.hash_changes(changes).await?;
+
+        self.repo
+            .update(id, changes, VersionPrecondition::OneOf(vec![user.version]))
+            .await
+            .map_err(|e| e.into())
+    }
+
+    /// Checks a password set through `update_user` against the policy, using
+    /// the incoming username and email where they change too.
+    async fn check_changed_password(
+        &self,
+        id: &str,
+        data: &UpdateUser,
+    ) -> Result<(), UserServiceError> {
+        let Some(password) = &data.password else {
+            return Ok(());
+        };
+
+        let user = self
+            .repo
+            .get_by_id(id.to_string())
+            .await?
+            .ok_or(UserServiceError::UserNotFound)?;
+
+        let username = data.username.as_deref().unwrap_or(&user.username);
+        let email = data.email.as_deref().unwrap_or(&user.email);
+
+        self.check_password(password, username, email)
+    }
+
+    /// Replaces a raw password in `data` with its hash.
+    async fn hash_changes(&self, data: UpdateUser) -> Result<UpdateUser, UserServiceError> {
+        let password = match data.password {
+            Some(password) => Some(self.hash(password).await?.as_str().to_string()),
+            None => None,
+        };
+
+        Ok(UpdateUser { password, ..data })
+    }
+
+    /// Sets a new password chosen by an administrator.
+    pub async fn reset_password(
+        &self,
+        id: String,
+        new_password: String,
+    ) -> Result<User, UserServiceError> {
+        let data = UpdateUser {
+            username: None,
+            email: None,
+            password: Some(new_password),
+        };
+
+        self.update_user(id, data, VersionPrecondition::Any).await
+    }
+
+    /// Replaces a user's own password once they have proven they know the
+    /// current one.
+    pub async fn change_password(
+        &self,
+        id: String,
+        current_password: String,
+        new_password: String,
+    ) -> Result<User, UserServiceError> {
+        let user = self
+            .repo
+            .get_by_id(id.clone())
+            .await?
+            .ok_or(UserServiceError::UserNotFound)?;
+
+        if self.verify(user.password.clone(), current_password).await? == Verification::Invalid {
+            return Err(UserServiceError::IncorrectPassword);
+        }
+
+        let data = UpdateUser {
+            username: None,
+            email: None,
+            password: Some(new_password),
+        };
+
+        self.update_user(id, data, VersionPrecondition::OneOf(vec![user.version]))
+            .await
     }
 
     pub async fn verify_user(
@@ -67,18 +362,59 @@ impl UserService {
         let user = self
             .repo
             .get_by_email(&email)
-            .await
+            .await?
             .ok_or(UserServiceError::UserNotFound)?;
 
-        if !user.password.verify(&raw_password) {
+        // Hashing arbitrarily long input is costly, and no stored password
+        // can exceed the policy maximum anyway.
+        if raw_password.chars().count() > self.password_policy.max_length() {
             return Err(UserServiceError::UserNotFound);
         }
 
-        Ok(user)
+        match self
+            .verify(user.password.clone(), raw_password.clone())
+            .await?
+        {
+            Verification::Invalid => Err(UserServiceError::UserNotFound),
+            Verification::Valid => Ok(user),
+            Verification::Outdated => {
+                self.rehash(&user, raw_password).await;
+                Ok(user)
+            }
+        }
     }
 
-    pub async fn find_by_id(&self, id: String) -> Option<User> {
-        self.repo.get_by_id(id).await
+    /// Upgrades a hash made with outdated parameters. Failures only cost the
+    /// upgrade, so they are logged rather than failing the login.
+    async fn rehash(&self, user: &User, raw_password: String) {
+        let hash = match self.hash(raw_password).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!(user_id = %user.id, "Failed to rehash password: {e}");
+                return;
+            }
+        };
+
+        match self
+            .repo
+            .replace_password_hash(user.id.clone(), user.password.clone(), hash)
+            .await
+        {
+            Ok(()) => info!(user_id = %user.id, "Password rehashed with current parameters"),
+            Err(e) => warn!(user_id = %user.id, "Failed to store rehashed password: {e}"),
+        }
+    }
+
+    pub async fn record_login(
+        &self,
+        id: String,
+        ip: Option<IpAddr>,
+    ) -> Result<(), UserServiceError> {
+        self.repo.record_login(id, ip).await.map_err(|e| e.into())
+    }
+
+    pub async fn find_by_id(&self, id: String) -> Result<Option<User>, UserServiceError> {
+        Ok(self.repo.get_by_id(id).await?)
     }
 }
 
@@ -89,6 +425,103 @@ impl From<UserRepositoryError> for UserServiceError {
             UserRepositoryError::NotFound => UserServiceError::UserNotFound,
             UserRepositoryError::Unknown => UserServiceError::Unknown,
             UserRepositoryError::QueryFailed(reason) => UserServiceError::RepositoryError(reason),
+            UserRepositoryError::VersionMismatch => UserServiceError::VersionMismatch,
+            UserRepositoryError::EmailTaken => UserServiceError::EmailTaken,
+            UserRepositoryError::Unavailable(reason) => UserServiceError::Unavailable(reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::settings::{PasswordHashingSettings, PasswordPolicySettings},
+        infra::db::memory_repo::InMemoryUserRepository,
+    };
+
+    fn hashing(iterations: u32, pepper: Option<(&str, &str)>) -> PasswordHashingSettings {
+        PasswordHashingSettings {
+            memory_kib: 64,
+            iterations,
+            pepper: pepper.map(|(_, pepper)| pepper.to_string()),
+            pepper_id: pepper.map_or("1", |(id, _)| id).to_string(),
+            ..Default::default()
         }
     }
+
+    /// A user whose password was hashed with `settings`, and a service
+    /// hashing with `current`.
+    async fn stored_with(
+        settings: PasswordHashingSettings,
+        current: &PasswordHashingSettings,
+    ) -> (Arc<InMemoryUserRepository>, User, UserService) {
+        let hash = PasswordHasher::new(&settings)
+            .unwrap()
+            .hash("correct horse")
+            .unwrap();
+        let repo = Arc::new(InMemoryUserRepository::new());
+        let user = repo
+            .create(NewUser {
+                username: "ana".to_string(),
+                email: "ana@example.com".to_string(),
+                password: hash.as_str().to_string(),
+                roles: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let policy = PasswordPolicy::new(PasswordPolicySettings::default()).unwrap();
+        let hasher = PasswordHasher::new(current).unwrap();
+        let service = UserService::new(repo.clone(), policy, hasher);
+        (repo, user, service)
+    }
+
+    async fn login(service: &UserService, password: &str) -> Result<User, UserServiceError> {
+        service
+            .verify_user("ana@example.com".to_string(), password.to_string())
+            .await
+    }
+
+    #[tokio::test]
+    async fn logins_rehash_outdated_passwords() {
+        let current = hashing(2, Some(("2", "new pepper")));
+        let outdated = [
+            hashing(1, None),
+            hashing(2, None),
+            hashing(1, Some(("2", "new pepper"))),
+        ];
+
+        for settings in outdated {
+            let (repo, user, service) = stored_with(settings, &current).await;
+
+            assert!(login(&service, "wrong").await.is_err());
+            let stored = repo.get_by_id(user.id.clone()).await.unwrap().unwrap();
+            assert_eq!(
+                stored.password, user.password,
+                "failed logins keep the hash"
+            );
+
+            login(&service, "correct horse").await.unwrap();
+            let stored = repo.get_by_id(user.id).await.unwrap().unwrap();
+            assert_ne!(stored.password, user.password);
+            assert_eq!(stored.version, user.version);
+            let verification = PasswordHasher::new(&current)
+                .unwrap()
+                .verify(&stored.password, "correct horse");
+            assert_eq!(verification, Verification::Valid);
+        }
+    }
+
+    #[tokio::test]
+    async fn logins_reject_hashes_with_an_unknown_key_id() {
+        let current = hashing(1, Some(("2", "new pepper")));
+        let old_pepper = hashing(1, Some(("1", "old pepper")));
+        let (repo, user, service) = stored_with(old_pepper, &current).await;
+
+        let rejected = login(&service, "correct horse").await;
+        assert!(matches!(rejected, Err(UserServiceError::UserNotFound)));
+        let stored = repo.get_by_id(user.id).await.unwrap().unwrap();
+        assert_eq!(stored.password, user.password);
+    }
 }
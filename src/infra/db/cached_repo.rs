@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rocket::async_trait;
+
+use crate::{
+    api::requests::PageConfig,
+    core::unit_of_work::{Operation, UnitOfWork},
+    core::user::{
+        dto::{NewUser, UpdateUser, VersionPrecondition},
+        model::{PasswordHash, User},
+        repo::{UserRepository, UserRepositoryError},
+    },
+    metrics::metrics,
+};
+
+/// Serves `get_by_id` from memory for `ttl`, dropping a user as soon as it
+/// is written through this repository. Writes made by other instances show
+/// up once the entry expires.
+pub struct CachedUserRepository {
+    inner: Arc<dyn UserRepository + Send + Sync>,
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    users: HashMap<String, (Instant, User)>,
+    /// Bumped by every invalidation. A lookup only fills the cache if no
+    /// write landed while it was reading, so it never restores a user that
+    /// a concurrent write has just dropped.
+    epoch: u64,
+}
+
+impl CachedUserRepository {
+    pub fn new(
+        inner: Arc<dyn UserRepository + Send + Sync>,
+        ttl: Duration,
+        max_entries: usize,
+    ) -> Self {
+        Self {
+            inner,
+            ttl,
+            max_entries,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Returns the cached user, or the epoch a fill for it has to match.
+    fn cached(&self, id: &str) -> Result<User, u64> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .users
+            .get(id)
+            .filter(|(expires_at, _)| Instant::now() < *expires_at)
+            .map(|(_, user)| user.clone())
+            .ok_or(entries.epoch)
+    }
+
+    fn store(&self, user: &User, epoch: u64) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.epoch != epoch {
+            return;
+        }
+        let users = &mut entries.users;
+        if users.len() >= self.max_entries {
+            let now = Instant::now();
+            users.retain(|_, (expires_at, _)| now < *expires_at);
+        }
+        if users.len() < self.max_entries {
+            users.insert(user.id.clone(), (Instant::now() + self.ttl, user.clone()));
+        }
+    }
+
+    fn invalidate(&self, id: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.users.remove(id);
+        entries.epoch += 1;
+    }
+}
+
+#[async_trait]
+impl UserRepository for CachedUserRepository {
+    async fn get_by_id(&self, id: String) -> Result<Option<User>, UserRepositoryError> {
+        let lookups = &metrics().user_cache_lookups;
+        let epoch = match self.cached(&id) {
+            Ok(user) => {
+                lookups.with_label_values(&["hit"]).inc();
+                return Ok(Some(user));
+            }
+            Err(epoch) => epoch,
+        };
+        lookups.with_label_values(&["miss"]).inc();
+
+        let user = self.inner.get_by_id(id).await?;
+        if let Some(user) = &user {
+            self.store(user, epoch);
+        }
+
+        Ok(user)
+    }
+
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, UserRepositoryError> {
+        self.inner.get_by_email(email).await
+    }
+
+    async fn create(&self, user: NewUser) -> Result<User, UserRepositoryError> {
+        self.inner.create(user).await
+    }
+
+    async fn create_many(&self, users: Vec<NewUser>) -> Result<Vec<User>, UserRepositoryError> {
+        self.inner.create_many(users).await
+    }
+
+    async fn update(
+        &self,
+        id: String,
+        data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserRepositoryError> {
+        let result = self.inner.update(id.clone(), data, precondition).await;
+        self.invalidate(&id);
+        result
+    }
+
+    async fn delete(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserRepositoryError> {
+        let result = self.inner.delete(id.clone(), precondition).await;
+        self.invalidate(&id);
+        result
+    }
+
+    async fn record_login(
+        &self,
+        id: String,
+        ip: Option<IpAddr>,
+    ) -> Result<(), UserRepositoryError> {
+        let result = self.inner.record_login(id.clone(), ip).await;
+        self.invalidate(&id);
+        result
+    }
+
+    async fn replace_password_hash(
+        &self,
+        id: String,
+        current: PasswordHash,
+        new: PasswordHash,
+    ) -> Result<(), UserRepositoryError> {
+        let result = self
+            .inner
+            .replace_password_hash(id.clone(), current, new)
+            .await;
+        self.invalidate(&id);
+        result
+    }
+
+    async fn list(&self, spec: PageConfig) -> Result<Vec<User>, UserRepositoryError> {
+        self.inner.list(spec).await
+    }
+
+    async fn commit(&self, work: UnitOfWork) -> Result<(), UserRepositoryError> {
+        let deleted: Vec<String> = work
+            .operations()
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::DeleteUser { id, .. } => Some(id.clone()),
+                Operation::CreateUser(_) => None,
+            })
+            .collect();
+
+        let result = self.inner.commit(work).await;
+        for id in &deleted {
+            self.invalidate(id);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::Notify;
+
+    use super::*;
+    use crate::infra::db::{hooked::HookedUserRepository, memory_repo::InMemoryUserRepository};
+
+    /// Holds each `get_by_id` result back until `release` is notified, so a
+    /// write can land while a lookup is still in flight.
+    fn paused(read: Arc<Notify>, release: Arc<Notify>) -> HookedUserRepository {
+        let users = Arc::new(InMemoryUserRepository::new());
+        HookedUserRepository::new(users, move |method| {
+            let (read, release) = (Arc::clone(&read), Arc::clone(&release));
+            async move {
+                if method == "get_by_id" {
+                    read.notify_one();
+                    release.notified().await;
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn lookups_racing_a_write_do_not_fill_the_cache() {
+        let read = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+        let paused = paused(Arc::clone(&read), Arc::clone(&release));
+        let cache = CachedUserRepository::new(Arc::new(paused), Duration::from_secs(60), 10);
+        let ana = cache
+            .create(NewUser {
+                username: "ana".to_string(),
+                email: "ana@example.com".to_string(),
+                password: "hash".to_string(),
+                roles: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let rename = async {
+            read.notified().await;
+            let update = UpdateUser {
+                username: Some("bea".to_string()),
+                email: None,
+                password: None,
+            };
+            cache
+                .update(ana.id.clone(), update, VersionPrecondition::Any)
+                .await
+                .unwrap();
+            release.notify_one();
+        };
+        let (stale, ()) = tokio::join!(cache.get_by_id(ana.id.clone()), rename);
+        assert_eq!(stale.unwrap().unwrap().username, "ana");
+
+        release.notify_one();
+        let current = cache.get_by_id(ana.id).await.unwrap().unwrap();
+        assert_eq!(current.username, "bea");
+    }
+
+    #[tokio::test]
+    async fn committed_deletes_drop_cached_users() {
+        let users = Arc::new(InMemoryUserRepository::new());
+        let cache = CachedUserRepository::new(users, Duration::from_secs(60), 10);
+        let ana = cache
+            .create(NewUser {
+                username: "ana".to_string(),
+                email: "ana@example.com".to_string(),
+                password: "hash".to_string(),
+                roles: Vec::new(),
+            })
+            .await
+            .unwrap();
+        assert!(cache.get_by_id(ana.id.clone()).await.unwrap().is_some());
+
+        let mut work = UnitOfWork::new();
+        work.delete_user(ana.id.clone(), VersionPrecondition::Any);
+        cache.commit(work).await.unwrap();
+
+        assert!(cache.get_by_id(ana.id).await.unwrap().is_none());
+    }
+}
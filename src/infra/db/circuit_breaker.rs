@@ -0,0 +1,146 @@
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single probe call is in flight; everyone else still fails fast
+    /// until it settles or, if it never does, until `until`.
+    HalfOpen {
+        until: Instant,
+    },
+}
+
+struct Circuit {
+    state: State,
+    /// Times the circuit has opened.
+    openings: u64,
+}
+
+/// A call the breaker let through. Its success only counts if the circuit
+/// has not opened since it started.
+#[derive(Debug, Clone, Copy)]
+pub struct Attempt {
+    openings: u64,
+}
+
+/// Fails calls fast once the database has failed `failure_threshold` times in
+/// a row, letting one probe through every `open_duration` to test recovery.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    circuit: Mutex<Circuit>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            open_duration,
+            circuit: Mutex::new(Circuit {
+                state: State::Closed { failures: 0 },
+                openings: 0,
+            }),
+        }
+    }
+
+    fn circuit(&self) -> MutexGuard<'_, Circuit> {
+        self.circuit.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Lets a call go ahead now, or `None` while the circuit fails fast.
+    pub fn allow(&self) -> Option<Attempt> {
+        let mut circuit = self.circuit();
+        match circuit.state {
+            State::Closed { .. } => {}
+            State::Open { until } | State::HalfOpen { until } if Instant::now() >= until => {
+                circuit.state = State::HalfOpen {
+                    until: Instant::now() + self.open_duration,
+                };
+            }
+            State::Open { .. } | State::HalfOpen { .. } => return None,
+        }
+
+        Some(Attempt {
+            openings: circuit.openings,
+        })
+    }
+
+    /// An attempt made regardless of the circuit, such as a reconnect.
+    pub fn attempt(&self) -> Attempt {
+        Attempt {
+            openings: self.circuit().openings,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(self.circuit().state, State::Closed { .. })
+    }
+
+    /// Closes the circuit, unless it opened after `attempt` started; a slow
+    /// call that outlived the failures says nothing about recovery.
+    pub fn record_success(&self, attempt: Attempt) {
+        let mut circuit = self.circuit();
+        if attempt.openings != circuit.openings {
+            return;
+        }
+
+        if !matches!(circuit.state, State::Closed { .. }) {
+            info!("Database circuit closed");
+        }
+        circuit.state = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        let mut circuit = self.circuit();
+        let failures = match circuit.state {
+            State::Closed { failures } => failures + 1,
+            State::HalfOpen { .. } => self.failure_threshold,
+            State::Open { .. } => return,
+        };
+
+        circuit.state = if failures >= self.failure_threshold {
+            warn!(
+                open_for_ms = self.open_duration.as_millis() as u64,
+                "Database circuit opened"
+            );
+            circuit.openings += 1;
+            State::Open {
+                until: Instant::now() + self.open_duration,
+            }
+        } else {
+            State::Closed { failures }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn successes_from_before_the_circuit_opened_are_ignored() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        let slow = breaker.allow().unwrap();
+
+        breaker.allow().unwrap();
+        breaker.record_failure();
+        assert!(breaker.is_open());
+
+        breaker.record_success(slow);
+        assert!(breaker.is_open());
+        assert!(breaker.allow().is_none());
+
+        breaker.record_success(breaker.attempt());
+        assert!(!breaker.is_open());
+    }
+}
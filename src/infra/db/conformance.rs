@@ -0,0 +1,380 @@
+//! Behaviour every [`UserRepository`] has to share, checked against each
+//! backend by handing [`check`] a factory for fresh, empty repositories.
+
+use std::{future::Future, net::IpAddr};
+
+use chrono::{Duration, Utc};
+
+use crate::{
+    api::requests::{PageConfig, SortOrder, Timestamp, UserSortField},
+    core::{
+        unit_of_work::UnitOfWork,
+        user::{
+            dto::{NewUser, UpdateUser, VersionPrecondition},
+            model::User,
+            repo::{UserRepository, UserRepositoryError},
+        },
+    },
+};
+
+/// Runs every check, each against a repository of its own.
+pub async fn check<R, F>(repository: impl Fn() -> F)
+where
+    R: UserRepository,
+    F: Future<Output = R>,
+{
+    creates_and_reads_back(&repository().await).await;
+    rejects_taken_emails(&repository().await).await;
+    updates_only_the_expected_version(&repository().await).await;
+    deletes_only_the_expected_version(&repository().await).await;
+    lists_pages_in_a_stable_order(&repository().await).await;
+    filters_lists(&repository().await).await;
+    records_logins(&repository().await).await;
+    commits_all_or_nothing(&repository().await).await;
+    commits_deletes_in_order(&repository().await).await;
+    rolls_back_unmatched_deletes(&repository().await).await;
+}
+
+fn new_user(name: &str) -> NewUser {
+    NewUser {
+        username: name.to_string(),
+        email: format!("{name}@example.com"),
+        password: format!("hash-of-{name}"),
+        roles: Vec::new(),
+    }
+}
+
+fn page(page: u32, per_page: u32) -> PageConfig {
+    PageConfig {
+        page: Some(page),
+        per_page: Some(per_page),
+        created_after: None,
+        created_before: None,
+        updated_after: None,
+        updated_before: None,
+        last_login_after: None,
+        last_login_before: None,
+        sort: None,
+        order: None,
+    }
+}
+
+fn sorted(sort: UserSortField, order: SortOrder) -> PageConfig {
+    PageConfig {
+        sort: Some(sort),
+        order: Some(order),
+        ..page(1, 100)
+    }
+}
+
+fn usernames(users: &[User]) -> Vec<&str> {
+    users.iter().map(|user| user.username.as_str()).collect()
+}
+
+fn update_username(username: &str) -> UpdateUser {
+    UpdateUser {
+        username: Some(username.to_string()),
+        email: None,
+        password: None,
+    }
+}
+
+async fn creates_and_reads_back(repo: &impl UserRepository) {
+    let created = repo.create(new_user("ana")).await.unwrap();
+    assert!(!created.id.is_empty());
+    assert_eq!(created.version, 1);
+
+    let by_id = repo.get_by_id(created.id.clone()).await.unwrap().unwrap();
+    assert_eq!(by_id.id, created.id);
+    assert_eq!(by_id.username, "ana");
+    assert_eq!(by_id.email, "ana@example.com");
+    assert_eq!(by_id.password.as_str(), "hash-of-ana");
+    assert_eq!(by_id.version, 1);
+    assert!(by_id.last_login_at.is_none());
+
+    let by_email = repo.get_by_email("ana@example.com").await.unwrap().unwrap();
+    assert_eq!(by_email.id, created.id);
+
+    assert!(
+        repo.get_by_id("missing".to_string())
+            .await
+            .unwrap()
+            .is_none()
+    );
+    let missing = repo.get_by_email("missing@example.com").await.unwrap();
+    assert!(missing.is_none());
+}
+
+async fn rejects_taken_emails(repo: &impl UserRepository) {
+    repo.create(new_user("ana")).await.unwrap();
+    let bea = repo.create(new_user("bea")).await.unwrap();
+
+    let taken = repo.create(new_user("ana")).await;
+    assert!(matches!(taken, Err(UserRepositoryError::EmailTaken)));
+
+    let batch = repo
+        .create_many(vec![new_user("cid"), new_user("ana")])
+        .await;
+    assert!(matches!(batch, Err(UserRepositoryError::EmailTaken)));
+    let cid = repo.get_by_email("cid@example.com").await.unwrap();
+    assert!(cid.is_none(), "a rejected batch must leave nothing behind");
+
+    let update = UpdateUser {
+        email: Some("ana@example.com".to_string()),
+        ..update_username("bea")
+    };
+    let taken = repo.update(bea.id, update, VersionPrecondition::Any).await;
+    assert!(matches!(taken, Err(UserRepositoryError::EmailTaken)));
+}
+
+async fn updates_only_the_expected_version(repo: &impl UserRepository) {
+    let ana = repo.create(new_user("ana")).await.unwrap();
+
+    let stale = repo
+        .update(
+            ana.id.clone(),
+            update_username("stale"),
+            VersionPrecondition::OneOf(vec![ana.version + 1]),
+        )
+        .await;
+    assert!(matches!(stale, Err(UserRepositoryError::VersionMismatch)));
+
+    let updated = repo
+        .update(
+            ana.id.clone(),
+            update_username("ana2"),
+            VersionPrecondition::OneOf(vec![ana.version]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.username, "ana2");
+    assert_eq!(updated.version, ana.version + 1);
+    assert!(updated.updated_at >= ana.updated_at);
+
+    let stored = repo.get_by_id(ana.id.clone()).await.unwrap().unwrap();
+    assert_eq!(stored.username, "ana2");
+    assert_eq!(stored.version, ana.version + 1);
+
+    let unconditional = repo
+        .update(ana.id, update_username("ana3"), VersionPrecondition::Any)
+        .await
+        .unwrap();
+    assert_eq!(unconditional.version, ana.version + 2);
+
+    let missing = repo
+        .update(
+            "missing".to_string(),
+            update_username("ghost"),
+            VersionPrecondition::Any,
+        )
+        .await;
+    assert!(matches!(missing, Err(UserRepositoryError::NotFound)));
+}
+
+async fn deletes_only_the_expected_version(repo: &impl UserRepository) {
+    let ana = repo.create(new_user("ana")).await.unwrap();
+
+    let stale = repo
+        .delete(
+            ana.id.clone(),
+            VersionPrecondition::OneOf(vec![ana.version + 1]),
+        )
+        .await;
+    assert!(matches!(stale, Err(UserRepositoryError::VersionMismatch)));
+    assert!(repo.get_by_id(ana.id.clone()).await.unwrap().is_some());
+
+    repo.delete(
+        ana.id.clone(),
+        VersionPrecondition::OneOf(vec![ana.version]),
+    )
+    .await
+    .unwrap();
+    assert!(repo.get_by_id(ana.id.clone()).await.unwrap().is_none());
+
+    let missing = repo.delete(ana.id, VersionPrecondition::Any).await;
+    assert!(matches!(missing, Err(UserRepositoryError::NotFound)));
+}
+
+async fn lists_pages_in_a_stable_order(repo: &impl UserRepository) {
+    for name in ["dan", "ana", "eve", "cid", "bea"] {
+        repo.create(new_user(name)).await.unwrap();
+    }
+
+    let ascending = repo
+        .list(sorted(UserSortField::Username, SortOrder::Asc))
+        .await
+        .unwrap();
+    assert_eq!(usernames(&ascending), ["ana", "bea", "cid", "dan", "eve"]);
+
+    let descending = repo
+        .list(sorted(UserSortField::Email, SortOrder::Desc))
+        .await
+        .unwrap();
+    assert_eq!(usernames(&descending), ["eve", "dan", "cid", "bea", "ana"]);
+
+    let second = repo
+        .list(PageConfig {
+            page: Some(2),
+            per_page: Some(2),
+            ..sorted(UserSortField::Username, SortOrder::Asc)
+        })
+        .await
+        .unwrap();
+    assert_eq!(usernames(&second), ["cid", "dan"]);
+
+    // Without a sort key, pages still partition the users.
+    let mut paged = Vec::new();
+    for number in 1..=3 {
+        paged.extend(repo.list(page(number, 2)).await.unwrap());
+    }
+    let mut names = usernames(&paged);
+    names.sort();
+    assert_eq!(names, ["ana", "bea", "cid", "dan", "eve"]);
+
+    assert!(repo.list(page(4, 2)).await.unwrap().is_empty());
+    assert!(repo.list(page(u32::MAX, 100)).await.unwrap().is_empty());
+}
+
+async fn filters_lists(repo: &impl UserRepository) {
+    let ana = repo.create(new_user("ana")).await.unwrap();
+    repo.create(new_user("bea")).await.unwrap();
+    repo.record_login(ana.id.clone(), None).await.unwrap();
+
+    let hour_ago = Timestamp(Utc::now() - Duration::hours(1));
+    let in_an_hour = Timestamp(Utc::now() + Duration::hours(1));
+    let listed = |spec: PageConfig| async move {
+        let mut users = repo.list(spec).await.unwrap();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        users
+            .into_iter()
+            .map(|user| user.username)
+            .collect::<Vec<_>>()
+    };
+
+    let created_recently = PageConfig {
+        created_after: Some(hour_ago),
+        created_before: Some(in_an_hour),
+        ..page(1, 10)
+    };
+    assert_eq!(listed(created_recently).await, ["ana", "bea"]);
+
+    let created_later = PageConfig {
+        created_after: Some(in_an_hour),
+        ..page(1, 10)
+    };
+    assert!(listed(created_later).await.is_empty());
+
+    let updated_earlier = PageConfig {
+        updated_before: Some(hour_ago),
+        ..page(1, 10)
+    };
+    assert!(listed(updated_earlier).await.is_empty());
+
+    let logged_in = PageConfig {
+        last_login_after: Some(hour_ago),
+        ..page(1, 10)
+    };
+    assert_eq!(listed(logged_in).await, ["ana"]);
+
+    let not_logged_in_lately = PageConfig {
+        last_login_before: Some(hour_ago),
+        ..page(1, 10)
+    };
+    assert_eq!(listed(not_logged_in_lately).await, ["bea"]);
+}
+
+async fn records_logins(repo: &impl UserRepository) {
+    let ana = repo.create(new_user("ana")).await.unwrap();
+    let ip: IpAddr = "192.0.2.7".parse().unwrap();
+
+    repo.record_login(ana.id.clone(), Some(ip)).await.unwrap();
+
+    let stored = repo.get_by_id(ana.id.clone()).await.unwrap().unwrap();
+    assert!(stored.last_login_at.is_some());
+    assert_eq!(stored.last_login_ip, Some(ip));
+    assert_eq!(stored.version, ana.version, "logins are not edits");
+
+    let missing = repo.record_login("missing".to_string(), Some(ip)).await;
+    assert!(matches!(missing, Err(UserRepositoryError::NotFound)));
+}
+
+async fn commits_all_or_nothing(repo: &impl UserRepository) {
+    repo.commit(UnitOfWork::new()).await.unwrap();
+
+    let mut work = UnitOfWork::new();
+    let ana = work.create_user(new_user("ana"));
+    let bea = work.create_user(new_user("bea"));
+    repo.commit(work).await.unwrap();
+
+    let stored = repo.get_by_id(ana.id).await.unwrap().unwrap();
+    assert_eq!(stored.email, "ana@example.com");
+    assert!(repo.get_by_id(bea.id).await.unwrap().is_some());
+
+    let mut work = UnitOfWork::new();
+    let cid = work.create_user(new_user("cid"));
+    work.create_user(new_user("ana"));
+    let rejected = repo.commit(work).await;
+    assert!(matches!(rejected, Err(UserRepositoryError::EmailTaken)));
+    assert!(
+        repo.get_by_id(cid.id).await.unwrap().is_none(),
+        "earlier operations roll back when a later one fails"
+    );
+}
+
+async fn commits_deletes_in_order(repo: &impl UserRepository) {
+    let ana = repo.create(new_user("ana")).await.unwrap();
+
+    // Deleting a user frees its email for a later create in the same work.
+    let mut work = UnitOfWork::new();
+    work.delete_user(
+        ana.id.clone(),
+        VersionPrecondition::OneOf(vec![ana.version]),
+    );
+    let replacement = work.create_user(new_user("ana"));
+    repo.commit(work).await.unwrap();
+    assert!(repo.get_by_id(ana.id).await.unwrap().is_none());
+    let stored = repo.get_by_email("ana@example.com").await.unwrap().unwrap();
+    assert_eq!(stored.id, replacement.id);
+
+    // A user created earlier in the same work can be deleted again.
+    let mut work = UnitOfWork::new();
+    let bea = work.create_user(new_user("bea"));
+    work.delete_user(
+        bea.id.clone(),
+        VersionPrecondition::OneOf(vec![bea.version]),
+    );
+    repo.commit(work).await.unwrap();
+    assert!(repo.get_by_id(bea.id).await.unwrap().is_none());
+}
+
+async fn rolls_back_unmatched_deletes(repo: &impl UserRepository) {
+    let ana = repo.create(new_user("ana")).await.unwrap();
+
+    let mut work = UnitOfWork::new();
+    let bea = work.create_user(new_user("bea"));
+    work.delete_user(
+        ana.id.clone(),
+        VersionPrecondition::OneOf(vec![ana.version + 1]),
+    );
+    let stale = repo.commit(work).await;
+    assert!(matches!(stale, Err(UserRepositoryError::VersionMismatch)));
+    assert!(repo.get_by_id(bea.id).await.unwrap().is_none());
+    assert!(repo.get_by_id(ana.id.clone()).await.unwrap().is_some());
+
+    let mut work = UnitOfWork::new();
+    work.delete_user(ana.id.clone(), VersionPrecondition::Any);
+    work.delete_user("missing".to_string(), VersionPrecondition::Any);
+    let missing = repo.commit(work).await;
+    assert!(matches!(missing, Err(UserRepositoryError::NotFound)));
+    assert!(
+        repo.get_by_id(ana.id.clone()).await.unwrap().is_some(),
+        "a delete rolls back when a later one fails"
+    );
+
+    let mut work = UnitOfWork::new();
+    work.delete_user(ana.id.clone(), VersionPrecondition::Any);
+    work.delete_user(ana.id.clone(), VersionPrecondition::Any);
+    let twice = repo.commit(work).await;
+    assert!(matches!(twice, Err(UserRepositoryError::NotFound)));
+    assert!(repo.get_by_id(ana.id).await.unwrap().is_some());
+}
@@ -1,31 +1,113 @@
 use std::sync::Arc;
+use std::time::Duration;
 use surrealdb::Surreal;
-use surrealdb::engine::remote::ws::{Client, Ws};
+use surrealdb::engine::any::{self, Any};
 use surrealdb::opt::auth::Root;
+use tokio::time::{sleep, timeout};
+use tracing::{info, warn};
 
 use crate::app::ApplicationError;
 use crate::config::settings::SurrealDbConfig;
+use crate::infra::db::circuit_breaker::CircuitBreaker;
 
+/// Connects to SurrealDB over the configured engine, retrying with
+/// exponential backoff so the service can start before the database is up.
 pub async fn create_surreal_client(
     cfg: &SurrealDbConfig,
-) -> Result<Arc<Surreal<Client>>, ApplicationError> {
-    let client = Surreal::new::<Ws>(&cfg.host)
-        .await
-        .map_err(|e| ApplicationError::DatabaseConnection(e.to_string()))?;
+) -> Result<Arc<Surreal<Any>>, ApplicationError> {
+    let resilience = &cfg.resilience;
+    let mut backoff = Duration::from_millis(resilience.initial_backoff_ms);
+    let mut attempt = 1;
+
+    loop {
+        match connect(cfg).await {
+            Ok(client) => return Ok(Arc::new(client)),
+            Err(e) if attempt < resilience.connect_attempts => {
+                warn!(
+                    attempt,
+                    retry_in_ms = backoff.as_millis() as u64,
+                    "Failed to connect database: {e}"
+                );
+                sleep(backoff).await;
+                backoff = next_backoff(backoff, cfg);
+                attempt += 1;
+            }
+            Err(e) => return Err(ApplicationError::DatabaseConnection(e)),
+        }
+    }
+}
+
+async fn connect(cfg: &SurrealDbConfig) -> Result<Surreal<Any>, String> {
+    let query_timeout = Duration::from_millis(cfg.resilience.query_timeout_ms);
 
-    client
-        .signin(Root {
-            username: &cfg.username,
-            password: &cfg.password,
-        })
+    let endpoint = cfg.endpoint();
+    let client = timeout(query_timeout, any::connect(endpoint.as_str()))
         .await
-        .map_err(|e| ApplicationError::DatabaseConnection(e.to_string()))?;
+        .map_err(|_| format!("no answer from {endpoint} within {query_timeout:?}"))?
+        .map_err(|e| e.to_string())?;
 
-    client
-        .use_ns(&cfg.namespace)
-        .use_db(&cfg.database)
+    timeout(query_timeout, authenticate(&client, cfg))
         .await
-        .map_err(|e| ApplicationError::DatabaseConnection(e.to_string()))?;
+        .map_err(|_| format!("sign-in timed out after {query_timeout:?}"))?
+        .map_err(|e| e.to_string())?;
+
+    Ok(client)
+}
+
+/// Signs in, unless the database is embedded, and selects the namespace and
+/// database.
+async fn authenticate(client: &Surreal<Any>, cfg: &SurrealDbConfig) -> surrealdb::Result<()> {
+    if !cfg.engine.is_embedded() {
+        client
+            .signin(Root {
+                username: &cfg.username,
+                password: &cfg.password,
+            })
+            .await?;
+    }
+
+    client.use_ns(&cfg.namespace).use_db(&cfg.database).await
+}
+
+fn next_backoff(backoff: Duration, cfg: &SurrealDbConfig) -> Duration {
+    (backoff * 2).min(Duration::from_millis(cfg.resilience.max_backoff_ms))
+}
+
+/// Watches the circuit breaker and, while it is open, signs in again on the
+/// connection the client re-establishes in the background, closing the
+/// circuit as soon as the database accepts the session.
+pub async fn supervise(
+    client: Arc<Surreal<Any>>,
+    cfg: SurrealDbConfig,
+    breaker: Arc<CircuitBreaker>,
+) {
+    let initial_backoff = Duration::from_millis(cfg.resilience.initial_backoff_ms);
+    let query_timeout = Duration::from_millis(cfg.resilience.query_timeout_ms);
+    let mut backoff = initial_backoff;
+
+    loop {
+        sleep(backoff).await;
+
+        if !breaker.is_open() {
+            backoff = initial_backoff;
+            continue;
+        }
 
-    Ok(Arc::new(client))
+        let attempt = breaker.attempt();
+        match timeout(query_timeout, authenticate(&client, &cfg)).await {
+            Ok(Ok(())) => {
+                info!("Database session re-established");
+                breaker.record_success(attempt);
+                backoff = initial_backoff;
+            }
+            Ok(Err(e)) => {
+                warn!("Database re-signin failed: {e}");
+                backoff = next_backoff(backoff, &cfg);
+            }
+            Err(_) => {
+                warn!("Database re-signin timed out");
+                backoff = next_backoff(backoff, &cfg);
+            }
+        }
+    }
 }
@@ -0,0 +1,113 @@
+//! A repository that delegates every call and then awaits a hook, so tests
+//! can step in between a call returning and its caller seeing the result.
+
+use std::{future::Future, net::IpAddr, pin::Pin, sync::Arc};
+
+use rocket::async_trait;
+
+use crate::{
+    api::requests::PageConfig,
+    core::unit_of_work::UnitOfWork,
+    core::user::{
+        dto::{NewUser, UpdateUser, VersionPrecondition},
+        model::{PasswordHash, User},
+        repo::{UserRepository, UserRepositoryError},
+    },
+};
+
+type Hook = Box<dyn Fn(&'static str) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+pub struct HookedUserRepository {
+    inner: Arc<dyn UserRepository + Send + Sync>,
+    hook: Hook,
+}
+
+impl HookedUserRepository {
+    /// Calls `hook` with the method name after each call into `inner`.
+    pub fn new<H, F>(inner: Arc<dyn UserRepository + Send + Sync>, hook: H) -> Self
+    where
+        H: Fn(&'static str) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            inner,
+            hook: Box::new(move |method| Box::pin(hook(method))),
+        }
+    }
+
+    async fn hooked<T>(&self, method: &'static str, call: impl Future<Output = T>) -> T {
+        let result = call.await;
+        (self.hook)(method).await;
+        result
+    }
+}
+
+#[async_trait]
+impl UserRepository for HookedUserRepository {
+    async fn get_by_id(&self, id: String) -> Result<Option<User>, UserRepositoryError> {
+        self.hooked("get_by_id", self.inner.get_by_id(id)).await
+    }
+
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, UserRepositoryError> {
+        self.hooked("get_by_email", self.inner.get_by_email(email))
+            .await
+    }
+
+    async fn create(&self, user: NewUser) -> Result<User, UserRepositoryError> {
+        self.hooked("create", self.inner.create(user)).await
+    }
+
+    async fn create_many(&self, users: Vec<NewUser>) -> Result<Vec<User>, UserRepositoryError> {
+        self.hooked("create_many", self.inner.create_many(users))
+            .await
+    }
+
+    async fn update(
+        &self,
+        id: String,
+        data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserRepositoryError> {
+        self.hooked("update", self.inner.update(id, data, precondition))
+            .await
+    }
+
+    async fn delete(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserRepositoryError> {
+        self.hooked("delete", self.inner.delete(id, precondition))
+            .await
+    }
+
+    async fn record_login(
+        &self,
+        id: String,
+        ip: Option<IpAddr>,
+    ) -> Result<(), UserRepositoryError> {
+        self.hooked("record_login", self.inner.record_login(id, ip))
+            .await
+    }
+
+    async fn replace_password_hash(
+        &self,
+        id: String,
+        current: PasswordHash,
+        new: PasswordHash,
+    ) -> Result<(), UserRepositoryError> {
+        self.hooked(
+            "replace_password_hash",
+            self.inner.replace_password_hash(id, current, new),
+        )
+        .await
+    }
+
+    async fn list(&self, spec: PageConfig) -> Result<Vec<User>, UserRepositoryError> {
+        self.hooked("list", self.inner.list(spec)).await
+    }
+
+    async fn commit(&self, work: UnitOfWork) -> Result<(), UserRepositoryError> {
+        self.hooked("commit", self.inner.commit(work)).await
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    net::IpAddr,
+    sync::RwLock,
+};
+
+use chrono::Utc;
+use rocket::async_trait;
+use uuid::Uuid;
+
+use crate::{
+    api::requests::{PageConfig, SortOrder, Timestamp, UserSortField},
+    core::unit_of_work::{Operation, UnitOfWork},
+    core::user::{
+        dto::{NewUser, UpdateUser, VersionPrecondition},
+        model::{PasswordHash, User},
+        repo::{UserRepository, UserRepositoryError},
+    },
+};
+
+/// Keeps users in process memory with the semantics of the SurrealDB
+/// repository, including the unique email index, for tests and demos.
+#[derive(Default)]
+pub struct InMemoryUserRepository {
+    users: RwLock<BTreeMap<String, User>>,
+}
+
+impl InMemoryUserRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<String, User>> {
+        self.users.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<String, User>> {
+        self.users.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn new_user(user: NewUser) -> User {
+    User::new(
+        Uuid::new_v4().simple().to_string(),
+        user.username,
+        user.email,
+        PasswordHash::from_hash(user.password),
+        user.roles,
+    )
+}
+
+/// Fails like the unique index on `users.email` when another user has `email`.
+fn ensure_email_free(
+    users: &BTreeMap<String, User>,
+    email: &str,
+    id: &str,
+) -> Result<(), UserRepositoryError> {
+    if users
+        .values()
+        .any(|user| user.email == email && user.id != id)
+    {
+        return Err(UserRepositoryError::EmailTaken);
+    }
+
+    Ok(())
+}
+
+fn ensure_version(
+    user: &User,
+    precondition: &VersionPrecondition,
+) -> Result<(), UserRepositoryError> {
+    match precondition {
+        VersionPrecondition::OneOf(versions) if !versions.contains(&user.version) => {
+            Err(UserRepositoryError::VersionMismatch)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Looks up the user a conditional write targets.
+fn matched<'a>(
+    users: &'a mut BTreeMap<String, User>,
+    id: &str,
+    precondition: &VersionPrecondition,
+) -> Result<&'a mut User, UserRepositoryError> {
+    let user = users.get_mut(id).ok_or(UserRepositoryError::NotFound)?;
+    ensure_version(user, precondition)?;
+    Ok(user)
+}
+
+/// Checks each operation against `users` as the earlier ones would leave
+/// them, so a commit can apply them all in place once this passes.
+fn validate(
+    users: &BTreeMap<String, User>,
+    operations: &[Operation],
+) -> Result<(), UserRepositoryError> {
+    let mut created: BTreeMap<&str, &User> = BTreeMap::new();
+    let mut deleted: HashSet<&str> = HashSet::new();
+
+    for operation in operations {
+        match operation {
+            Operation::CreateUser(user) => {
+                let taken = users
+                    .values()
+                    .filter(|other| !deleted.contains(other.id.as_str()))
+                    .chain(created.values().copied())
+                    .any(|other| other.email == user.email && other.id != user.id);
+                if taken {
+                    return Err(UserRepositoryError::EmailTaken);
+                }
+                created.insert(&user.id, user);
+            }
+            Operation::DeleteUser { id, precondition } => {
+                let user = match created.remove(id.as_str()) {
+                    Some(user) => user,
+                    None if deleted.contains(id.as_str()) => {
+                        return Err(UserRepositoryError::NotFound);
+                    }
+                    None => users.get(id).ok_or(UserRepositoryError::NotFound)?,
+                };
+                ensure_version(user, precondition)?;
+                deleted.insert(id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn filtered(user: &User, spec: &PageConfig) -> bool {
+    let after = |value: Option<_>, bound: Option<Timestamp>| {
+        bound.is_none_or(|bound| value.is_some_and(|value| value >= bound.0))
+    };
+    let before = |value, bound: Option<Timestamp>| bound.is_none_or(|bound| value < bound.0);
+
+    after(Some(user.created_at), spec.created_after)
+        && before(user.created_at, spec.created_before)
+        && after(Some(user.updated_at), spec.updated_after)
+        && before(user.updated_at, spec.updated_before)
+        && after(user.last_login_at, spec.last_login_after)
+        && spec.last_login_before.is_none_or(|bound| {
+            user.last_login_at
+                .is_none_or(|last_login_at| last_login_at < bound.0)
+        })
+}
+
+fn compare(a: &User, b: &User, sort: UserSortField) -> Ordering {
+    match sort {
+        UserSortField::Username => a.username.cmp(&b.username),
+        UserSortField::Email => a.email.cmp(&b.email),
+        UserSortField::CreatedAt => a.created_at.cmp(&b.created_at),
+        UserSortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+        UserSortField::LastLoginAt => a.last_login_at.cmp(&b.last_login_at),
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn get_by_id(&self, id: String) -> Result<Option<User>, UserRepositoryError> {
+        Ok(self.read().get(&id).cloned())
+    }
+
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, UserRepositoryError> {
+        Ok(self
+            .read()
+            .values()
+            .find(|user| user.email == email)
+            .cloned())
+    }
+
+    async fn create(&self, user: NewUser) -> Result<User, UserRepositoryError> {
+        let mut users = self.write();
+        let user = new_user(user);
+        ensure_email_free(&users, &user.email, &user.id)?;
+
+        users.insert(user.id.clone(), user.clone());
+        Ok(user)
+    }
+
+    async fn create_many(&self, users: Vec<NewUser>) -> Result<Vec<User>, UserRepositoryError> {
+        let mut stored = self.write();
+        let mut staged = stored.clone();
+        let mut created = Vec::with_capacity(users.len());
+
+        for user in users {
+            let user = new_user(user);
+            ensure_email_free(&staged, &user.email, &user.id)?;
+            staged.insert(user.id.clone(), user.clone());
+            created.push(user);
+        }
+
+        *stored = staged;
+        Ok(created)
+    }
+
+    async fn update(
+        &self,
+        id: String,
+        data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserRepositoryError> {
+        let mut users = self.write();
+        matched(&mut users, &id, &precondition)?;
+        if let Some(email) = &data.email {
+            ensure_email_free(&users, email, &id)?;
+        }
+
+        let user = matched(&mut users, &id, &precondition)?;
+        if let Some(username) = data.username {
+            user.username = username;
+        }
+        if let Some(email) = data.email {
+            user.email = email;
+        }
+        if let Some(password) = data.password {
+            user.password = PasswordHash::from_hash(password);
+        }
+        user.version += 1;
+        user.updated_at = Utc::now();
+
+        Ok(user.clone())
+    }
+
+    async fn delete(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserRepositoryError> {
+        let mut users = self.write();
+        matched(&mut users, &id, &precondition)?;
+        users.remove(&id);
+
+        Ok(())
+    }
+
+    async fn record_login(
+        &self,
+        id: String,
+        ip: Option<IpAddr>,
+    ) -> Result<(), UserRepositoryError> {
+        let mut users = self.write();
+        let user = users.get_mut(&id).ok_or(UserRepositoryError::NotFound)?;
+        user.last_login_at = Some(Utc::now());
+        user.last_login_ip = ip;
+
+        Ok(())
+    }
+
+    async fn replace_password_hash(
+        &self,
+        id: String,
+        current: PasswordHash,
+        new: PasswordHash,
+    ) -> Result<(), UserRepositoryError> {
+        if let Some(user) = self.write().get_mut(&id)
+            && user.password == current
+        {
+            user.password = new;
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, spec: PageConfig) -> Result<Vec<User>, UserRepositoryError> {
+        let per_page = spec.per_page();
+        let start = spec.start();
+
+        let mut users: Vec<User> = self
+            .read()
+            .values()
+            .filter(|user| filtered(user, &spec))
+            .cloned()
+            .collect();
+
+        if let Some(sort) = spec.sort {
+            let order = spec.order.unwrap_or_default();
+            users.sort_by(|a, b| {
+                match order {
+                    SortOrder::Asc => compare(a, b, sort),
+                    SortOrder::Desc => compare(b, a, sort),
+                }
+                .then_with(|| a.id.cmp(&b.id))
+            });
+        }
+
+        Ok(users
+            .into_iter()
+            .skip(usize::try_from(start).unwrap_or(usize::MAX))
+            .take(per_page as usize)
+            .collect())
+    }
+
+    async fn commit(&self, work: UnitOfWork) -> Result<(), UserRepositoryError> {
+        let operations = work.into_operations();
+        let mut users = self.write();
+        validate(&users, &operations)?;
+
+        for operation in operations {
+            match operation {
+                Operation::CreateUser(user) => {
+                    users.insert(user.id.clone(), user);
+                }
+                Operation::DeleteUser { id, .. } => {
+                    users.remove(&id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::db::conformance;
+
+    #[tokio::test]
+    async fn conforms() {
+        conformance::check(|| async { InMemoryUserRepository::new() }).await;
+    }
+}
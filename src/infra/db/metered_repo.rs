@@ -0,0 +1,102 @@
+use std::{net::IpAddr, sync::Arc, time::Instant};
+
+use rocket::async_trait;
+
+use crate::{
+    api::requests::PageConfig,
+    core::unit_of_work::UnitOfWork,
+    core::user::{
+        dto::{NewUser, UpdateUser, VersionPrecondition},
+        model::{PasswordHash, User},
+        repo::{UserRepository, UserRepositoryError},
+    },
+    metrics::metrics,
+};
+
+/// Records the latency of every call into the wrapped repository.
+pub struct MeteredUserRepository {
+    inner: Arc<dyn UserRepository + Send + Sync>,
+}
+
+impl MeteredUserRepository {
+    pub fn new(inner: Arc<dyn UserRepository + Send + Sync>) -> Self {
+        Self { inner }
+    }
+}
+
+async fn timed<T>(method: &str, call: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let result = call.await;
+
+    metrics()
+        .repository_query_duration
+        .with_label_values(&[method])
+        .observe(start.elapsed().as_secs_f64());
+
+    result
+}
+
+#[async_trait]
+impl UserRepository for MeteredUserRepository {
+    async fn get_by_id(&self, id: String) -> Result<Option<User>, UserRepositoryError> {
+        timed("get_by_id", self.inner.get_by_id(id)).await
+    }
+
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, UserRepositoryError> {
+        timed("get_by_email", self.inner.get_by_email(email)).await
+    }
+
+    async fn create(&self, user: NewUser) -> Result<User, UserRepositoryError> {
+        timed("create", self.inner.create(user)).await
+    }
+
+    async fn create_many(&self, users: Vec<NewUser>) -> Result<Vec<User>, UserRepositoryError> {
+        timed("create_many", self.inner.create_many(users)).await
+    }
+
+    async fn update(
+        &self,
+        id: String,
+        data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserRepositoryError> {
+        timed("update", self.inner.update(id, data, precondition)).await
+    }
+
+    async fn delete(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserRepositoryError> {
+        timed("delete", self.inner.delete(id, precondition)).await
+    }
+
+    async fn record_login(
+        &self,
+        id: String,
+        ip: Option<IpAddr>,
+    ) -> Result<(), UserRepositoryError> {
+        timed("record_login", self.inner.record_login(id, ip)).await
+    }
+
+    async fn replace_password_hash(
+        &self,
+        id: String,
+        current: PasswordHash,
+        new: PasswordHash,
+    ) -> Result<(), UserRepositoryError> {
+        timed(
+            "replace_password_hash",
+            self.inner.replace_password_hash(id, current, new),
+        )
+        .await
+    }
+
+    async fn list(&self, spec: PageConfig) -> Result<Vec<User>, UserRepositoryError> {
+        timed("list", self.inner.list(spec)).await
+    }
+
+    async fn commit(&self, work: UnitOfWork) -> Result<(), UserRepositoryError> {
+        timed("commit", self.inner.commit(work)).await
+    }
+}
@@ -0,0 +1,277 @@
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use surrealdb::{Surreal, engine::any::Any};
+use thiserror::Error;
+use tracing::info;
+
+use crate::app::ApplicationError;
+use crate::config::settings::{MigrationMode, MigrationSettings};
+use crate::infra::db::transaction;
+
+/// Bookkeeping table; created on the first run that applies anything.
+const DEFINE_MIGRATIONS_TABLE: &str = "
+    DEFINE TABLE IF NOT EXISTS _migrations SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS version ON _migrations TYPE int;
+    DEFINE FIELD IF NOT EXISTS name ON _migrations TYPE string;
+    DEFINE FIELD IF NOT EXISTS checksum ON _migrations TYPE string;
+    DEFINE FIELD IF NOT EXISTS applied_at ON _migrations TYPE datetime;
+";
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("Failed to read migrations from {0}: {1}")]
+    Read(String, String),
+
+    #[error("Migration file name must look like `0001_description.surql`: {0}")]
+    InvalidName(String),
+
+    #[error("More than one migration has version {0}")]
+    DuplicateVersion(u32),
+
+    #[error("Migration {version} ({name}) was changed after it was applied")]
+    ChecksumMismatch { version: u32, name: String },
+
+    #[error("Migration {version} ({name}) failed: {reason}")]
+    Failed {
+        version: u32,
+        name: String,
+        reason: String,
+    },
+
+    #[error("Failed to read applied migrations: {0}")]
+    Database(String),
+}
+
+/// A `.surql` script, identified by the version prefix of its file name.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: u32,
+    pub name: String,
+    /// SHA-256 of the script, so edits to an applied migration are caught.
+    pub checksum: String,
+    pub script: String,
+}
+
+#[derive(Deserialize)]
+struct AppliedMigration {
+    version: u32,
+    checksum: String,
+}
+
+pub struct Migrator {
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    /// Reads every `<version>_<name>.surql` file in `directory`, ordered by
+    /// version.
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self, MigrationError> {
+        let directory = directory.as_ref();
+        let read_error = |e: std::io::Error| {
+            MigrationError::Read(directory.display().to_string(), e.to_string())
+        };
+
+        let mut migrations = Vec::new();
+        for entry in fs::read_dir(directory).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != "surql")
+            {
+                continue;
+            }
+
+            let file_name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            let (version, name) = file_name
+                .split_once('_')
+                .and_then(|(version, name)| Some((version.parse().ok()?, name)))
+                .ok_or_else(|| MigrationError::InvalidName(path.display().to_string()))?;
+            let script = fs::read_to_string(&path).map_err(read_error)?;
+
+            migrations.push(Migration {
+                version,
+                name: name.to_string(),
+                checksum: format!("{:x}", Sha256::digest(script.as_bytes())),
+                script,
+            });
+        }
+
+        migrations.sort_by_key(|migration| migration.version);
+        if let Some(pair) = migrations
+            .windows(2)
+            .find(|pair| pair[0].version == pair[1].version)
+        {
+            return Err(MigrationError::DuplicateVersion(pair[0].version));
+        }
+
+        Ok(Self { migrations })
+    }
+
+    /// Migrations not applied yet, after checking that none of the applied
+    /// ones has been edited since.
+    pub async fn pending(&self, client: &Surreal<Any>) -> Result<Vec<&Migration>, MigrationError> {
+        let applied: Vec<AppliedMigration> = client
+            .query("SELECT version, checksum FROM _migrations")
+            .await
+            .map_err(database_error)?
+            .take(0)
+            .map_err(database_error)?;
+
+        let mut pending = Vec::new();
+        for migration in &self.migrations {
+            match applied.iter().find(|a| a.version == migration.version) {
+                Some(a) if a.checksum != migration.checksum => {
+                    return Err(MigrationError::ChecksumMismatch {
+                        version: migration.version,
+                        name: migration.name.clone(),
+                    });
+                }
+                Some(_) => {}
+                None => pending.push(migration),
+            }
+        }
+
+        Ok(pending)
+    }
+
+    /// Applies pending migrations in order, each in its own transaction
+    /// together with its `_migrations` record.
+    pub async fn migrate(&self, client: &Surreal<Any>) -> Result<Vec<&Migration>, MigrationError> {
+        let pending = self.pending(client).await?;
+        if pending.is_empty() {
+            return Ok(pending);
+        }
+
+        client
+            .query(DEFINE_MIGRATIONS_TABLE)
+            .await
+            .map_err(database_error)?
+            .check()
+            .map_err(database_error)?;
+
+        for migration in &pending {
+            apply(client, migration).await?;
+            info!(
+                version = migration.version,
+                name = %migration.name,
+                "Applied migration"
+            );
+        }
+
+        Ok(pending)
+    }
+}
+
+fn database_error(e: surrealdb::Error) -> MigrationError {
+    MigrationError::Database(e.to_string())
+}
+
+async fn apply(client: &Surreal<Any>, migration: &Migration) -> Result<(), MigrationError> {
+    let script = migration.script.trim_end();
+    let separator = if script.ends_with(';') { "" } else { ";" };
+    let query = format!(
+        "BEGIN TRANSACTION;\n{script}{separator}\n\
+         CREATE type::thing('_migrations', $version) CONTENT {{ \
+         version: $version, name: $name, checksum: $checksum, applied_at: time::now() \
+         }};\nCOMMIT TRANSACTION;"
+    );
+
+    let failed = |reason: String| MigrationError::Failed {
+        version: migration.version,
+        name: migration.name.clone(),
+        reason,
+    };
+
+    let mut response = client
+        .query(query)
+        .bind(("version", migration.version))
+        .bind(("name", migration.name.clone()))
+        .bind(("checksum", migration.checksum.clone()))
+        .await
+        .map_err(|e| failed(e.to_string()))?;
+
+    match transaction::failure(&mut response) {
+        Some(reason) => Err(failed(reason)),
+        None => Ok(()),
+    }
+}
+
+/// Brings the schema up to date at startup, or, in strict mode, refuses to
+/// start while migrations are pending.
+pub async fn migrate_on_startup(
+    client: &Surreal<Any>,
+    settings: &MigrationSettings,
+) -> Result<(), ApplicationError> {
+    if settings.mode == MigrationMode::Off {
+        return Ok(());
+    }
+
+    let migrator = Migrator::load(&settings.directory)?;
+    match settings.mode {
+        MigrationMode::Apply => {
+            migrator.migrate(client).await?;
+        }
+        MigrationMode::Strict => {
+            let pending = migrator.pending(client).await?;
+            if !pending.is_empty() {
+                let versions = pending
+                    .iter()
+                    .map(|migration| format!("{:04}_{}", migration.version, migration.name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Err(ApplicationError::PendingMigrations(versions));
+            }
+        }
+        MigrationMode::Off => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use surrealdb::engine::any::connect;
+
+    use super::*;
+    use crate::{core::user::repo::UserRepository, infra::db::user_repo::SurrealUserRepository};
+
+    #[tokio::test]
+    async fn backfills_users_written_before_the_schema() {
+        let client = connect("mem://").await.unwrap();
+        client.use_ns("test").use_db("test").await.unwrap();
+        client
+            .query(
+                "CREATE users:legacy CONTENT {
+                    username: 'legacy',
+                    email: 'legacy@example.com',
+                    password: 'hash',
+                    roles: ['User'],
+                }",
+            )
+            .await
+            .unwrap()
+            .check()
+            .unwrap();
+
+        Migrator::load("migrations")
+            .unwrap()
+            .migrate(&client)
+            .await
+            .unwrap();
+
+        let user = SurrealUserRepository::new(Arc::new(client))
+            .get_by_id("legacy".to_string())
+            .await
+            .unwrap()
+            .expect("legacy user is readable after migrating");
+        assert_eq!(user.version, 1);
+        assert_eq!(user.created_at, user.updated_at);
+    }
+}
@@ -1,2 +1,15 @@
+pub mod cached_repo;
+pub mod circuit_breaker;
+#[cfg(test)]
+mod conformance;
 pub mod connection;
+#[cfg(test)]
+pub mod hooked;
+pub mod memory_repo;
+pub mod metered_repo;
+pub mod migrations;
+pub mod resilient_repo;
+pub mod sql_connection;
+pub mod sql_repo;
+pub mod transaction;
 pub mod user_repo;
@@ -0,0 +1,215 @@
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use rocket::async_trait;
+
+use crate::{
+    api::requests::PageConfig,
+    core::unit_of_work::UnitOfWork,
+    core::user::{
+        dto::{NewUser, UpdateUser, VersionPrecondition},
+        model::{PasswordHash, User},
+        repo::{UserRepository, UserRepositoryError},
+    },
+    infra::db::circuit_breaker::CircuitBreaker,
+};
+
+/// Fails fast with `Unavailable` while the circuit breaker is open, and
+/// bounds reads and writes by their own timeouts. A timeout counts as a
+/// failure towards opening the circuit.
+///
+/// The database may still apply a write after the caller gave up on it, so
+/// writes get a longer timeout than reads, and a timed-out write reports
+/// that its outcome is unknown.
+pub struct ResilientUserRepository {
+    inner: Arc<dyn UserRepository + Send + Sync>,
+    breaker: Arc<CircuitBreaker>,
+    read_timeout: Duration,
+    write_timeout: Duration,
+}
+
+impl ResilientUserRepository {
+    pub fn new(
+        inner: Arc<dyn UserRepository + Send + Sync>,
+        breaker: Arc<CircuitBreaker>,
+        read_timeout: Duration,
+        write_timeout: Duration,
+    ) -> Self {
+        Self {
+            inner,
+            breaker,
+            read_timeout,
+            write_timeout,
+        }
+    }
+
+    async fn read<T>(
+        &self,
+        call: impl Future<Output = Result<T, UserRepositoryError>>,
+    ) -> Result<T, UserRepositoryError> {
+        let timeout = self.read_timeout;
+        self.guarded(async move {
+            tokio::time::timeout(timeout, call)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(UserRepositoryError::Unavailable(format!(
+                        "no answer within {}ms",
+                        timeout.as_millis()
+                    )))
+                })
+        })
+        .await
+    }
+
+    async fn write<T>(
+        &self,
+        call: impl Future<Output = Result<T, UserRepositoryError>>,
+    ) -> Result<T, UserRepositoryError> {
+        let timeout = self.write_timeout;
+        self.guarded(async move {
+            tokio::time::timeout(timeout, call)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(UserRepositoryError::Unavailable(format!(
+                        "no answer within {}ms; the write may still have been applied",
+                        timeout.as_millis()
+                    )))
+                })
+        })
+        .await
+    }
+
+    async fn guarded<T>(
+        &self,
+        call: impl Future<Output = Result<T, UserRepositoryError>>,
+    ) -> Result<T, UserRepositoryError> {
+        let Some(attempt) = self.breaker.allow() else {
+            return Err(UserRepositoryError::Unavailable(
+                "circuit breaker is open".to_string(),
+            ));
+        };
+
+        match call.await {
+            Err(UserRepositoryError::Unavailable(reason)) => {
+                self.breaker.record_failure();
+                Err(UserRepositoryError::Unavailable(reason))
+            }
+            result => {
+                self.breaker.record_success(attempt);
+                result
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepository for ResilientUserRepository {
+    async fn get_by_id(&self, id: String) -> Result<Option<User>, UserRepositoryError> {
+        self.read(self.inner.get_by_id(id)).await
+    }
+
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, UserRepositoryError> {
+        self.read(self.inner.get_by_email(email)).await
+    }
+
+    async fn create(&self, user: NewUser) -> Result<User, UserRepositoryError> {
+        self.write(self.inner.create(user)).await
+    }
+
+    async fn create_many(&self, users: Vec<NewUser>) -> Result<Vec<User>, UserRepositoryError> {
+        self.write(self.inner.create_many(users)).await
+    }
+
+    async fn update(
+        &self,
+        id: String,
+        data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserRepositoryError> {
+        self.write(self.inner.update(id, data, precondition)).await
+    }
+
+    async fn delete(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserRepositoryError> {
+        self.write(self.inner.delete(id, precondition)).await
+    }
+
+    async fn record_login(
+        &self,
+        id: String,
+        ip: Option<IpAddr>,
+    ) -> Result<(), UserRepositoryError> {
+        self.write(self.inner.record_login(id, ip)).await
+    }
+
+    async fn replace_password_hash(
+        &self,
+        id: String,
+        current: PasswordHash,
+        new: PasswordHash,
+    ) -> Result<(), UserRepositoryError> {
+        self.write(self.inner.replace_password_hash(id, current, new))
+            .await
+    }
+
+    async fn list(&self, spec: PageConfig) -> Result<Vec<User>, UserRepositoryError> {
+        self.read(self.inner.list(spec)).await
+    }
+
+    async fn commit(&self, work: UnitOfWork) -> Result<(), UserRepositoryError> {
+        self.write(self.inner.commit(work)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+
+    use super::*;
+    use crate::infra::db::{hooked::HookedUserRepository, memory_repo::InMemoryUserRepository};
+
+    fn new_user(username: &str) -> NewUser {
+        NewUser {
+            username: username.to_string(),
+            email: format!("{username}@example.com"),
+            password: "hash".to_string(),
+            roles: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_time_out_and_open_the_circuit() {
+        // The write lands, but its answer never comes back.
+        let stalled = HookedUserRepository::new(
+            Arc::new(InMemoryUserRepository::new()),
+            |method| async move {
+                if method == "create" {
+                    future::pending::<()>().await;
+                }
+            },
+        );
+        let breaker = Arc::new(CircuitBreaker::new(1, Duration::from_secs(60)));
+        let repo = ResilientUserRepository::new(
+            Arc::new(stalled),
+            Arc::clone(&breaker),
+            Duration::from_secs(60),
+            Duration::from_millis(20),
+        );
+
+        let Err(UserRepositoryError::Unavailable(reason)) = repo.create(new_user("ana")).await
+        else {
+            panic!("a stalled write should time out");
+        };
+        assert!(reason.contains("may still have been applied"), "{reason}");
+        assert!(breaker.is_open());
+
+        let Err(UserRepositoryError::Unavailable(reason)) =
+            repo.get_by_email("ana@example.com").await
+        else {
+            panic!("an open circuit should fail fast");
+        };
+        assert_eq!(reason, "circuit breaker is open");
+    }
+}
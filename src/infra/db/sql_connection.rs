@@ -0,0 +1,121 @@
+use sqlx::{
+    AnyPool,
+    any::{AnyPoolOptions, install_default_drivers},
+    migrate::{Migrate, MigrateError, Migration, Migrator},
+};
+use tracing::info;
+
+use crate::app::ApplicationError;
+use crate::config::settings::{DatabaseBackend, MigrationMode, SqlSettings};
+use crate::infra::db::migrations::MigrationError;
+
+/// Schema for the SQLite and PostgreSQL backends, embedded at build time.
+pub static SQL_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sql");
+
+/// Opens a pool for `database.sql.url`, which must use the scheme of the
+/// selected backend.
+pub async fn create_sql_pool(
+    backend: DatabaseBackend,
+    cfg: &SqlSettings,
+) -> Result<AnyPool, ApplicationError> {
+    let schemes: &[&str] = match backend {
+        DatabaseBackend::Sqlite => &["sqlite:"],
+        DatabaseBackend::Postgres => &["postgres:", "postgresql:"],
+        DatabaseBackend::SurrealDb | DatabaseBackend::Memory => &[],
+    };
+    if !schemes.iter().any(|scheme| cfg.url.starts_with(scheme)) {
+        return Err(ApplicationError::DatabaseConnection(format!(
+            "database.sql.url must start with {} for the {backend:?} backend",
+            schemes.join(" or ")
+        )));
+    }
+
+    install_default_drivers();
+    AnyPoolOptions::new()
+        .max_connections(cfg.max_connections)
+        .connect(&cfg.url)
+        .await
+        .map_err(|e| ApplicationError::DatabaseConnection(e.to_string()))
+}
+
+/// SQL migrations not applied to `pool` yet.
+pub async fn pending_sql_migrations(
+    pool: &AnyPool,
+) -> Result<Vec<&'static Migration>, MigrationError> {
+    let mut conn = pool.acquire().await.map_err(sql_error)?;
+    conn.ensure_migrations_table()
+        .await
+        .map_err(migrate_error)?;
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .map_err(migrate_error)?;
+
+    Ok(SQL_MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .filter(|migration| !applied.iter().any(|a| a.version == migration.version))
+        .collect())
+}
+
+/// Applies pending SQL migrations; sqlx rejects ones edited after being
+/// applied.
+pub async fn run_sql_migrations(pool: &AnyPool) -> Result<Vec<&'static Migration>, MigrationError> {
+    let pending = pending_sql_migrations(pool).await?;
+    SQL_MIGRATOR.run(pool).await.map_err(migrate_error)?;
+
+    for migration in &pending {
+        info!(
+            version = migration.version,
+            name = %migration.description,
+            "Applied migration"
+        );
+    }
+
+    Ok(pending)
+}
+
+/// The SQL counterpart of
+/// [`migrate_on_startup`](crate::infra::db::migrations::migrate_on_startup).
+pub async fn migrate_sql_on_startup(
+    pool: &AnyPool,
+    mode: MigrationMode,
+) -> Result<(), ApplicationError> {
+    match mode {
+        MigrationMode::Apply => {
+            run_sql_migrations(pool).await?;
+        }
+        MigrationMode::Strict => {
+            let pending = pending_sql_migrations(pool).await?;
+            if !pending.is_empty() {
+                let versions = pending
+                    .iter()
+                    .map(|migration| format!("{:04}_{}", migration.version, migration.description))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Err(ApplicationError::PendingMigrations(versions));
+            }
+        }
+        MigrationMode::Off => {}
+    }
+
+    Ok(())
+}
+
+fn sql_error(e: sqlx::Error) -> MigrationError {
+    MigrationError::Database(e.to_string())
+}
+
+fn migrate_error(e: MigrateError) -> MigrationError {
+    match e {
+        MigrateError::VersionMismatch(version) => MigrationError::ChecksumMismatch {
+            version: version as u32,
+            name: SQL_MIGRATOR
+                .iter()
+                .find(|migration| migration.version == version)
+                .map(|migration| migration.description.to_string())
+                .unwrap_or_default(),
+        },
+        e => MigrationError::Database(e.to_string()),
+    }
+}
@@ -0,0 +1,532 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rocket::async_trait;
+use sqlx::{Any, AnyConnection, AnyPool, Executor, FromRow, error::DatabaseError};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    api::requests::{PageConfig, SortOrder},
+    auth::roles::Role,
+    core::unit_of_work::{Operation, UnitOfWork},
+    core::user::{
+        dto::{NewUser, UpdateUser, VersionPrecondition},
+        model::{PasswordHash, User},
+        repo::{UserRepository, UserRepositoryError},
+    },
+};
+
+/// A `users` row; timestamps are RFC 3339 text and roles a JSON array, which
+/// SQLite and PostgreSQL store alike.
+#[derive(FromRow)]
+struct UserRow {
+    id: String,
+    username: String,
+    email: String,
+    password: String,
+    roles: String,
+    version: i64,
+    created_at: String,
+    updated_at: String,
+    last_login_at: Option<String>,
+    last_login_ip: Option<String>,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserRepositoryError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        let corrupt = |field: &str, e: String| {
+            UserRepositoryError::QueryFailed(format!("Invalid {field} for user {}: {e}", row.id))
+        };
+
+        Ok(User {
+            roles: serde_json::from_str::<Vec<Role>>(&row.roles)
+                .map_err(|e| corrupt("roles", e.to_string()))?,
+            version: row.version as u64,
+            created_at: parse_timestamp(&row.created_at)
+                .map_err(|e| corrupt("created_at", e.to_string()))?,
+            updated_at: parse_timestamp(&row.updated_at)
+                .map_err(|e| corrupt("updated_at", e.to_string()))?,
+            last_login_at: row
+                .last_login_at
+                .as_deref()
+                .map(parse_timestamp)
+                .transpose()
+                .map_err(|e| corrupt("last_login_at", e.to_string()))?,
+            last_login_ip: row
+                .last_login_ip
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(|e: std::net::AddrParseError| corrupt("last_login_ip", e.to_string()))?,
+            password: PasswordHash::from_hash(row.password),
+            id: row.id,
+            username: row.username,
+            email: row.email,
+        })
+    }
+}
+
+/// Fixed-width UTC, so comparing the text compares the instants.
+fn timestamp(value: DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    DateTime::parse_from_rfc3339(value).map(|value| value.with_timezone(&Utc))
+}
+
+/// Stores users in SQLite or PostgreSQL through the sqlx `Any` driver.
+pub struct SqlUserRepository {
+    pool: AnyPool,
+    system: &'static str,
+}
+
+impl SqlUserRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        let system = if pool.connect_options().database_url.scheme() == "sqlite" {
+            "sqlite"
+        } else {
+            "postgresql"
+        };
+
+        Self { pool, system }
+    }
+
+    /// Tells apart a missing record from one whose version failed the precondition.
+    async fn unmatched(&self, id: String) -> UserRepositoryError {
+        match self.get_by_id(id).await {
+            Ok(Some(_)) => UserRepositoryError::VersionMismatch,
+            Ok(None) => UserRepositoryError::NotFound,
+            Err(e) => e,
+        }
+    }
+
+    async fn fetch_one_by(
+        &self,
+        column: &str,
+        value: String,
+    ) -> Result<Option<User>, UserRepositoryError> {
+        let query = format!("SELECT * FROM users WHERE {column} = $1 LIMIT 1");
+        sqlx::query_as::<_, UserRow>(&query)
+            .bind(value)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?
+            .map(User::try_from)
+            .transpose()
+    }
+}
+
+/// Maps connection failures to `Unavailable`, so callers can tell an outage
+/// from a failing query.
+fn query_error(
+    e: sqlx::Error,
+    otherwise: fn(String) -> UserRepositoryError,
+) -> UserRepositoryError {
+    match e {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => UserRepositoryError::Unavailable(e.to_string()),
+        sqlx::Error::Database(ref db) if violates_email_index(db.as_ref()) => {
+            UserRepositoryError::EmailTaken
+        }
+        e => otherwise(e.to_string()),
+    }
+}
+
+/// Whether a write collided with the unique `users_email` index rather than
+/// another unique constraint such as the primary key.
+fn violates_email_index(db: &dyn DatabaseError) -> bool {
+    db.is_unique_violation()
+        && match db.constraint() {
+            Some(name) => name == "users_email",
+            // SQLite names the columns instead: "UNIQUE constraint failed: users.email".
+            None => db.message().ends_with("users.email"),
+        }
+}
+
+/// `AND version IN (...)` with placeholders numbered from `first`, and the
+/// versions to bind to them.
+fn version_condition(precondition: VersionPrecondition, first: usize) -> (String, Vec<i64>) {
+    match precondition {
+        VersionPrecondition::Any => (String::new(), Vec::new()),
+        VersionPrecondition::OneOf(versions) if versions.is_empty() => {
+            (" AND 1 = 0".to_string(), Vec::new())
+        }
+        VersionPrecondition::OneOf(versions) => {
+            let placeholders = (first..first + versions.len())
+                .map(|n| format!("${n}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            (
+                format!(" AND version IN ({placeholders})"),
+                versions.into_iter().map(|v| v as i64).collect(),
+            )
+        }
+    }
+}
+
+async fn insert<'e>(
+    executor: impl Executor<'e, Database = Any>,
+    user: &User,
+) -> Result<(), UserRepositoryError> {
+    let roles = serde_json::to_string(&user.roles)
+        .map_err(|e| UserRepositoryError::QueryFailed(e.to_string()))?;
+
+    sqlx::query(
+        "INSERT INTO users (id, username, email, password, roles, version, created_at, updated_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(user.id.clone())
+    .bind(user.username.clone())
+    .bind(user.email.clone())
+    .bind(user.password.as_str().to_string())
+    .bind(roles)
+    .bind(user.version as i64)
+    .bind(timestamp(user.created_at))
+    .bind(timestamp(user.updated_at))
+    .execute(executor)
+    .await
+    .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+    Ok(())
+}
+
+/// Deletes user `id` if it meets `precondition`, and otherwise tells apart
+/// a missing user from a version mismatch on the same connection, so the
+/// answer holds inside a transaction.
+async fn delete_matched(
+    conn: &mut AnyConnection,
+    id: String,
+    precondition: VersionPrecondition,
+) -> Result<(), UserRepositoryError> {
+    let (condition, versions) = version_condition(precondition, 2);
+    let query = format!("DELETE FROM users WHERE id = $1{condition}");
+
+    let mut query = sqlx::query(&query).bind(id.clone());
+    for version in versions {
+        query = query.bind(version);
+    }
+
+    let result = query
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
+    if result.rows_affected() > 0 {
+        return Ok(());
+    }
+
+    let exists = sqlx::query("SELECT id FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
+
+    match exists {
+        Some(_) => Err(UserRepositoryError::VersionMismatch),
+        None => Err(UserRepositoryError::NotFound),
+    }
+}
+
+fn new_user(user: NewUser) -> User {
+    User::new(
+        Uuid::new_v4().simple().to_string(),
+        user.username,
+        user.email,
+        PasswordHash::from_hash(user.password),
+        user.roles,
+    )
+}
+
+#[async_trait]
+impl UserRepository for SqlUserRepository {
+    #[instrument(name = "sql.get_by_id", skip_all, fields(db.system = self.system))]
+    async fn get_by_id(&self, id: String) -> Result<Option<User>, UserRepositoryError> {
+        self.fetch_one_by("id", id).await
+    }
+
+    #[instrument(name = "sql.get_by_email", skip_all, fields(db.system = self.system))]
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, UserRepositoryError> {
+        self.fetch_one_by("email", email.to_string()).await
+    }
+
+    #[instrument(name = "sql.create", skip_all, fields(db.system = self.system))]
+    async fn create(&self, user: NewUser) -> Result<User, UserRepositoryError> {
+        let user = new_user(user);
+        insert(&self.pool, &user).await?;
+
+        Ok(user)
+    }
+
+    #[instrument(name = "sql.create_many", skip_all, fields(db.system = self.system))]
+    async fn create_many(&self, users: Vec<NewUser>) -> Result<Vec<User>, UserRepositoryError> {
+        let users: Vec<User> = users.into_iter().map(new_user).collect();
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+        for user in &users {
+            insert(&mut *tx, user).await?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+        Ok(users)
+    }
+
+    #[instrument(name = "sql.update", skip_all, fields(db.system = self.system))]
+    async fn update(
+        &self,
+        id: String,
+        data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserRepositoryError> {
+        let mut assignments = vec![
+            "version = version + 1".to_string(),
+            "updated_at = $2".to_string(),
+        ];
+        let mut values = Vec::new();
+
+        let fields = [
+            ("username", data.username),
+            ("email", data.email),
+            ("password", data.password),
+        ];
+        for (column, value) in fields {
+            if let Some(value) = value {
+                values.push(value);
+                assignments.push(format!("{column} = ${}", values.len() + 2));
+            }
+        }
+
+        let (condition, versions) = version_condition(precondition, values.len() + 3);
+        let query = format!(
+            "UPDATE users SET {} WHERE id = $1{condition} RETURNING *",
+            assignments.join(", ")
+        );
+
+        let mut query = sqlx::query_as::<_, UserRow>(&query)
+            .bind(id.clone())
+            .bind(timestamp(Utc::now()));
+        for value in values {
+            query = query.bind(value);
+        }
+        for version in versions {
+            query = query.bind(version);
+        }
+
+        let row = query
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+        match row {
+            Some(row) => User::try_from(row),
+            None => Err(self.unmatched(id).await),
+        }
+    }
+
+    #[instrument(name = "sql.delete", skip_all, fields(db.system = self.system))]
+    async fn delete(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserRepositoryError> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
+
+        delete_matched(&mut conn, id, precondition).await
+    }
+
+    #[instrument(name = "sql.record_login", skip_all, fields(db.system = self.system))]
+    async fn record_login(
+        &self,
+        id: String,
+        ip: Option<IpAddr>,
+    ) -> Result<(), UserRepositoryError> {
+        let result =
+            sqlx::query("UPDATE users SET last_login_at = $2, last_login_ip = $3 WHERE id = $1")
+                .bind(id)
+                .bind(timestamp(Utc::now()))
+                .bind(ip.map(|ip| ip.to_string()))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+        match result.rows_affected() {
+            0 => Err(UserRepositoryError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[instrument(name = "sql.replace_password_hash", skip_all, fields(db.system = self.system))]
+    async fn replace_password_hash(
+        &self,
+        id: String,
+        current: PasswordHash,
+        new: PasswordHash,
+    ) -> Result<(), UserRepositoryError> {
+        sqlx::query("UPDATE users SET password = $3 WHERE id = $1 AND password = $2")
+            .bind(id)
+            .bind(current.as_str().to_string())
+            .bind(new.as_str().to_string())
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))
+    }
+
+    #[instrument(name = "sql.list", skip_all, fields(db.system = self.system))]
+    async fn list(&self, spec: PageConfig) -> Result<Vec<User>, UserRepositoryError> {
+        let per_page = spec.per_page();
+        let start = spec.start();
+
+        let mut conditions = Vec::new();
+        let mut bindings = Vec::new();
+
+        let ranges = [
+            ("created_at", ">=", spec.created_after),
+            ("created_at", "<", spec.created_before),
+            ("updated_at", ">=", spec.updated_after),
+            ("updated_at", "<", spec.updated_before),
+            ("last_login_at", ">=", spec.last_login_after),
+        ];
+
+        for (field, op, value) in ranges {
+            if let Some(value) = value {
+                bindings.push(timestamp(value.0));
+                conditions.push(format!("{field} {op} ${}", bindings.len()));
+            }
+        }
+
+        if let Some(value) = spec.last_login_before {
+            bindings.push(timestamp(value.0));
+            conditions.push(format!(
+                "(last_login_at < ${} OR last_login_at IS NULL)",
+                bindings.len()
+            ));
+        }
+
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+
+        // Missing values sort first ascending, as in SurrealDB, and `id`
+        // breaks ties so consecutive pages neither skip nor repeat users.
+        let ordering = match spec.sort {
+            Some(sort) => match spec.order.unwrap_or_default() {
+                SortOrder::Asc => format!(" ORDER BY {} ASC NULLS FIRST, id", sort.column()),
+                SortOrder::Desc => format!(" ORDER BY {} DESC NULLS LAST, id", sort.column()),
+            },
+            None => " ORDER BY id".to_string(),
+        };
+
+        let query = format!(
+            "SELECT * FROM users{filter}{ordering} LIMIT ${} OFFSET ${}",
+            bindings.len() + 1,
+            bindings.len() + 2
+        );
+
+        let mut query = sqlx::query_as::<_, UserRow>(&query);
+        for binding in bindings {
+            query = query.bind(binding);
+        }
+
+        query
+            .bind(i64::from(per_page))
+            .bind(i64::try_from(start).unwrap_or(i64::MAX))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?
+            .into_iter()
+            .map(User::try_from)
+            .collect()
+    }
+
+    #[instrument(name = "sql.commit", skip_all, fields(db.system = self.system))]
+    async fn commit(&self, work: UnitOfWork) -> Result<(), UserRepositoryError> {
+        // Dropping the transaction on an early return rolls it back.
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+        for operation in work.into_operations() {
+            match operation {
+                Operation::CreateUser(user) => insert(&mut *tx, &user).await?,
+                Operation::DeleteUser { id, precondition } => {
+                    delete_matched(&mut tx, id, precondition).await?
+                }
+            }
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::any::{AnyPoolOptions, install_default_drivers};
+
+    use super::*;
+    use crate::infra::db::{conformance, sql_connection::run_sql_migrations};
+
+    /// Set to a PostgreSQL URL to run the suite against it too. Its `users`
+    /// table is emptied before each check.
+    const POSTGRES_URL_VAR: &str = "TEST_POSTGRES_URL";
+
+    async fn migrated(url: &str) -> AnyPool {
+        install_default_drivers();
+        // One connection that never closes, since every connection to
+        // `sqlite::memory:` opens a database of its own.
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect(url)
+            .await
+            .unwrap();
+        run_sql_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn sqlite_conforms() {
+        conformance::check(|| async { SqlUserRepository::new(migrated("sqlite::memory:").await) })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn postgres_conforms() {
+        let Ok(url) = std::env::var(POSTGRES_URL_VAR) else {
+            eprintln!("{POSTGRES_URL_VAR} is not set, skipping");
+            return;
+        };
+
+        conformance::check(|| async {
+            let pool = migrated(&url).await;
+            sqlx::query("DELETE FROM users")
+                .execute(&pool)
+                .await
+                .unwrap();
+            SqlUserRepository::new(pool)
+        })
+        .await;
+    }
+}
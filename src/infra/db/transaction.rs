@@ -0,0 +1,16 @@
+use surrealdb::Response;
+
+/// Why a SurrealDB transaction failed, if it did.
+///
+/// Every statement of a failed transaction reports an error; the earliest
+/// one that is not the generic cancellation names the actual cause.
+pub fn failure(response: &mut Response) -> Option<String> {
+    let mut errors: Vec<_> = response.take_errors().into_iter().collect();
+    errors.sort_by_key(|(index, _)| *index);
+
+    errors
+        .iter()
+        .map(|(_, e)| e.to_string())
+        .find(|e| !e.contains("failed transaction"))
+        .or_else(|| errors.first().map(|(_, e)| e.to_string()))
+}
@@ -1,65 +1,168 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
 
+use chrono::Utc;
 use rocket::async_trait;
-use surrealdb::{Surreal, engine::remote::ws::Client};
-use tracing::error;
+use serde::Serialize;
+use surrealdb::{Surreal, engine::any::Any, error::Api as ApiError, sql::Datetime};
+use tracing::instrument;
 
 use crate::{
     api::requests::PageConfig,
+    auth::roles::Role,
+    core::unit_of_work::{Operation, UnitOfWork},
     core::user::{
-        dto::{NewUser, UpdateUser},
+        dto::{NewUser, UpdateUser, VersionPrecondition},
         model::{PasswordHash, User},
         repo::{UserRepository, UserRepositoryError},
     },
+    infra::db::transaction,
 };
 
+#[derive(Serialize)]
+struct NewUserRecord {
+    username: String,
+    email: String,
+    password: String,
+    roles: Vec<Role>,
+    version: u64,
+    created_at: Datetime,
+    updated_at: Datetime,
+}
+
+impl From<NewUser> for NewUserRecord {
+    fn from(user: NewUser) -> Self {
+        let now = Datetime::from(Utc::now());
+
+        Self {
+            username: user.username,
+            email: user.email,
+            password: user.password,
+            roles: user.roles,
+            version: 1,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+}
+
+impl From<&User> for NewUserRecord {
+    fn from(user: &User) -> Self {
+        Self {
+            username: user.username.clone(),
+            email: user.email.clone(),
+            password: user.password.as_str().to_string(),
+            roles: user.roles.clone(),
+            version: user.version,
+            created_at: Datetime::from(user.created_at),
+            updated_at: Datetime::from(user.updated_at),
+        }
+    }
+}
+
 pub struct SurrealUserRepository {
-    client: Arc<Surreal<Client>>,
+    client: Arc<Surreal<Any>>,
 }
 
 impl SurrealUserRepository {
-    pub fn new(client: Arc<Surreal<Client>>) -> Self {
+    pub fn new(client: Arc<Surreal<Any>>) -> Self {
         Self { client }
     }
+
+    /// Tells apart a missing record from one whose version failed the precondition.
+    async fn unmatched(&self, id: String) -> UserRepositoryError {
+        match self.get_by_id(id).await {
+            Ok(Some(_)) => UserRepositoryError::VersionMismatch,
+            Ok(None) => UserRepositoryError::NotFound,
+            Err(e) => e,
+        }
+    }
+}
+
+/// Maps transport failures to `Unavailable`, so callers can tell an outage
+/// from a failing query.
+fn query_error(
+    e: surrealdb::Error,
+    otherwise: fn(String) -> UserRepositoryError,
+) -> UserRepositoryError {
+    match e {
+        surrealdb::Error::Api(
+            ApiError::Ws(_) | ApiError::Http(_) | ApiError::ConnectionUninitialised,
+        ) => UserRepositoryError::Unavailable(e.to_string()),
+        e if violates_email_index(&e.to_string()) => UserRepositoryError::EmailTaken,
+        e => otherwise(e.to_string()),
+    }
+}
+
+/// Whether a failed write collided with the unique `users_email` index.
+///
+/// SurrealDB 2.3 reports this only as `Error::IndexExists` rendered to
+/// text ("Database index `users_email` already contains ..."), and the
+/// client API does not expose the variant, so the message is matched.
+/// Recheck it when upgrading the `surrealdb` crate.
+fn violates_email_index(reason: &str) -> bool {
+    reason.contains("index `users_email` already contains")
+}
+
+/// Raised inside a commit's transaction when a staged delete finds no user.
+const NO_SUCH_USER: &str = "unit of work: no such user";
+/// Raised inside a commit's transaction when a staged delete's precondition fails.
+const STALE_USER: &str = "unit of work: stale user version";
+
+/// `WHERE` clause matching the versions bound to the parameter `versions`.
+fn version_condition(precondition: &VersionPrecondition, versions: &str) -> String {
+    match precondition {
+        VersionPrecondition::Any => String::new(),
+        VersionPrecondition::OneOf(_) => format!(" WHERE (version OR 0) IN ${versions}"),
+    }
+}
+
+fn expected_versions(precondition: VersionPrecondition) -> Vec<u64> {
+    match precondition {
+        VersionPrecondition::Any => Vec::new(),
+        VersionPrecondition::OneOf(versions) => versions,
+    }
 }
 
 #[async_trait]
 impl UserRepository for SurrealUserRepository {
-    async fn get_by_id(&self, id: String) -> Option<User> {
-        let id = format!("users:{id}");
-        let query = format!("SELECT * FROM users WHERE id = {id} LIMIT 1;");
-
+    #[instrument(name = "surrealdb.get_by_id", skip_all, fields(db.system = "surrealdb"))]
+    async fn get_by_id(&self, id: String) -> Result<Option<User>, UserRepositoryError> {
         let mut response = self
             .client
-            .query(query)
+            .query("SELECT * FROM type::thing('users', $id)")
+            .bind(("id", id))
             .await
-            .map_err(|e| UserRepositoryError::QueryFailed(e.to_string()))
-            .inspect_err(|e| error!("{:?}", e))
-            .ok()?;
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
 
-        let user: Option<User> = response
+        response
             .take(0)
-            .map_err(|_| UserRepositoryError::NotFound)
-            .ok()?;
-
-        user
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))
     }
 
-    async fn get_by_email(&self, email: &str) -> Option<User> {
-        let query = format!("SELECT * FROM users WHERE email = '{email}' LIMIT 1");
-        let mut response = self.client.query(query).await.ok()?;
-        let users: Vec<User> = response.take(0).ok()?;
+    #[instrument(name = "surrealdb.get_by_email", skip_all, fields(db.system = "surrealdb"))]
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, UserRepositoryError> {
+        let mut response = self
+            .client
+            .query("SELECT * FROM users WHERE email = $email LIMIT 1")
+            .bind(("email", email.to_string()))
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
+
+        let users: Vec<User> = response
+            .take(0)
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
 
-        users.into_iter().next()
+        Ok(users.into_iter().next())
     }
 
+    #[instrument(name = "surrealdb.create", skip_all, fields(db.system = "surrealdb"))]
     async fn create(&self, new_user: NewUser) -> Result<User, UserRepositoryError> {
         let mut response: Option<User> = self
             .client
             .create("users")
-            .content(new_user)
+            .content(NewUserRecord::from(new_user))
             .await
-            .map_err(|e| UserRepositoryError::DatabaseError(e.to_string()))?;
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
 
         let created_user = response.take().ok_or(UserRepositoryError::QueryFailed(
             "User creation failed".into(),
@@ -68,58 +171,354 @@ impl UserRepository for SurrealUserRepository {
         Ok(created_user)
     }
 
-    async fn update(&self, id: String, data: UpdateUser) -> Result<User, UserRepositoryError> {
+    #[instrument(name = "surrealdb.create_many", skip_all, fields(db.system = "surrealdb"))]
+    async fn create_many(&self, users: Vec<NewUser>) -> Result<Vec<User>, UserRepositoryError> {
+        let records: Vec<NewUserRecord> = users.into_iter().map(NewUserRecord::from).collect();
+
+        let mut response = self
+            .client
+            .query("BEGIN TRANSACTION; INSERT INTO users $users; COMMIT TRANSACTION;")
+            .bind(("users", records))
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+        response
+            .take(0)
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))
+    }
+
+    #[instrument(name = "surrealdb.update", skip_all, fields(db.system = "surrealdb"))]
+    async fn update(
+        &self,
+        id: String,
+        data: UpdateUser,
+        precondition: VersionPrecondition,
+    ) -> Result<User, UserRepositoryError> {
         let mut fields = HashMap::new();
 
         if let Some(name) = data.username {
             fields.insert("username", surrealdb::sql::Value::from(name.to_string()));
         }
 
+        if let Some(email) = data.email {
+            fields.insert("email", surrealdb::sql::Value::from(email));
+        }
+
         if let Some(password) = data.password {
-            let password = PasswordHash::raw(password)
-                .map_err(|e| UserRepositoryError::QueryFailed(e.to_string()))?;
+            fields.insert("password", surrealdb::sql::Value::from(password));
+        }
 
-            let password = password.as_str();
+        fields.insert(
+            "updated_at",
+            surrealdb::sql::Value::from(Datetime::from(Utc::now())),
+        );
 
-            fields.insert("password", surrealdb::sql::Value::from(password));
+        let assignments = fields
+            .keys()
+            .map(|field| format!(", {field} = ${field}"))
+            .collect::<String>();
+
+        let query = format!(
+            "UPDATE type::thing('users', $id) SET version = (version OR 0) + 1{assignments}{} RETURN AFTER",
+            version_condition(&precondition, "versions")
+        );
+
+        let mut query = self
+            .client
+            .query(query)
+            .bind(("id", id.clone()))
+            .bind(("versions", expected_versions(precondition)));
+
+        for (field, value) in fields {
+            query = query.bind((field, value));
         }
 
+        let mut response = query
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+        let user: Option<User> = response
+            .take(0)
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
+
+        match user {
+            Some(user) => Ok(user),
+            None => Err(self.unmatched(id).await),
+        }
+    }
+
+    #[instrument(name = "surrealdb.delete", skip_all, fields(db.system = "surrealdb"))]
+    async fn delete(
+        &self,
+        id: String,
+        precondition: VersionPrecondition,
+    ) -> Result<(), UserRepositoryError> {
+        let query = format!(
+            "DELETE type::thing('users', $id){} RETURN BEFORE",
+            version_condition(&precondition, "versions")
+        );
+
+        let mut response = self
+            .client
+            .query(query)
+            .bind(("id", id.clone()))
+            .bind(("versions", expected_versions(precondition)))
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
+
+        let user: Option<User> = response
+            .take(0)
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
+
+        match user {
+            Some(_) => Ok(()),
+            None => Err(self.unmatched(id).await),
+        }
+    }
+
+    #[instrument(name = "surrealdb.record_login", skip_all, fields(db.system = "surrealdb"))]
+    async fn record_login(
+        &self,
+        id: String,
+        ip: Option<IpAddr>,
+    ) -> Result<(), UserRepositoryError> {
+        let mut fields = HashMap::new();
+
+        fields.insert(
+            "last_login_at",
+            surrealdb::sql::Value::from(Datetime::from(Utc::now())),
+        );
+        fields.insert(
+            "last_login_ip",
+            ip.map(|ip| surrealdb::sql::Value::from(ip.to_string()))
+                .unwrap_or(surrealdb::sql::Value::None),
+        );
+
         let user: Option<User> = self
             .client
-            .update(("users", id.to_string().as_str()))
+            .update(("users", id.as_str()))
             .merge(fields)
             .await
-            .map_err(|e| UserRepositoryError::DatabaseError(e.to_string()))?;
-
-        let user = user.ok_or(UserRepositoryError::NotFound)?;
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
 
-        Ok(user)
+        user.map(|_| ()).ok_or(UserRepositoryError::NotFound)
     }
 
-    async fn delete(&self, id: String) -> Result<(), UserRepositoryError> {
-        self.client
-            .delete(("users", id.to_string()))
+    #[instrument(name = "surrealdb.replace_password_hash", skip_all, fields(db.system = "surrealdb"))]
+    async fn replace_password_hash(
+        &self,
+        id: String,
+        current: PasswordHash,
+        new: PasswordHash,
+    ) -> Result<(), UserRepositoryError> {
+        let response = self
+            .client
+            .query("UPDATE type::thing('users', $id) SET password = $new WHERE password = $current")
+            .bind(("id", id))
+            .bind(("current", current.as_str().to_string()))
+            .bind(("new", new.as_str().to_string()))
             .await
-            .map_err(|e| UserRepositoryError::QueryFailed(e.to_string()))?
-            .ok_or(UserRepositoryError::NotFound)
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+        response
+            .check()
+            .map(|_| ())
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))
     }
 
+    #[instrument(name = "surrealdb.list", skip_all, fields(db.system = "surrealdb"))]
     async fn list(&self, spec: PageConfig) -> Result<Vec<User>, UserRepositoryError> {
-        let page = spec.page.unwrap_or(1);
-        let per_page = spec.per_page.unwrap_or(10);
-        let start = (page - 1) * per_page;
+        let per_page = spec.per_page();
+        // SurrealDB rejects a START past u32::MAX; no table reaches that far.
+        let Ok(start) = u32::try_from(spec.start()) else {
+            return Ok(Vec::new());
+        };
 
-        let query = format!("SELECT * FROM users START {start} LIMIT {per_page}");
+        let mut conditions = Vec::new();
+        let mut bindings = Vec::new();
 
-        let mut response = self
-            .client
-            .query(query)
+        let ranges = [
+            ("created_at", ">=", "created_after", spec.created_after),
+            ("created_at", "<", "created_before", spec.created_before),
+            ("updated_at", ">=", "updated_after", spec.updated_after),
+            ("updated_at", "<", "updated_before", spec.updated_before),
+            (
+                "last_login_at",
+                ">=",
+                "last_login_after",
+                spec.last_login_after,
+            ),
+        ];
+
+        for (field, op, param, value) in ranges {
+            if let Some(value) = value {
+                conditions.push(format!("{field} {op} ${param}"));
+                bindings.push((param, Datetime::from(value.0)));
+            }
+        }
+
+        if let Some(value) = spec.last_login_before {
+            conditions
+                .push("(last_login_at < $last_login_before OR last_login_at IS NONE)".to_string());
+            bindings.push(("last_login_before", Datetime::from(value.0)));
+        }
+
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+
+        // `id` breaks ties, so consecutive pages neither skip nor repeat users.
+        let ordering = match spec.sort {
+            Some(sort) => {
+                let order = spec.order.unwrap_or_default();
+                format!(" ORDER BY {} {}, id ASC", sort.column(), order.keyword())
+            }
+            None => " ORDER BY id ASC".to_string(),
+        };
+
+        let query = format!("SELECT * FROM users{filter}{ordering} START {start} LIMIT {per_page}");
+
+        let mut query = self.client.query(query);
+
+        for binding in bindings {
+            query = query.bind(binding);
+        }
+
+        let mut response = query
             .await
-            .map_err(|e| UserRepositoryError::QueryFailed(e.to_string()))?;
-        let users: Vec<User> = response
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))?;
+        response
             .take(0)
-            .map_err(|_| UserRepositoryError::NotFound)?;
+            .map_err(|e| query_error(e, UserRepositoryError::QueryFailed))
+    }
+
+    #[instrument(name = "surrealdb.commit", skip_all, fields(db.system = "surrealdb"))]
+    async fn commit(&self, work: UnitOfWork) -> Result<(), UserRepositoryError> {
+        if work.is_empty() {
+            return Ok(());
+        }
+
+        let operations = work.into_operations();
+        let statements = operations
+            .iter()
+            .enumerate()
+            .map(|(i, operation)| match operation {
+                Operation::CreateUser(_) => {
+                    format!("CREATE type::thing('users', $id_{i}) CONTENT $user_{i};")
+                }
+                // Statements that match nothing succeed, so a delete that
+                // cannot apply throws to abort the transaction.
+                Operation::DeleteUser { precondition, .. } => format!(
+                    "IF !(SELECT VALUE id FROM type::thing('users', $id_{i})) {{ THROW '{NO_SUCH_USER}' }};\
+                     IF !(DELETE type::thing('users', $id_{i}){} RETURN BEFORE) {{ THROW '{STALE_USER}' }};",
+                    version_condition(precondition, &format!("versions_{i}"))
+                ),
+            })
+            .collect::<String>();
+
+        let mut query = self
+            .client
+            .query(format!("BEGIN TRANSACTION;{statements}COMMIT TRANSACTION;"));
+
+        for (i, operation) in operations.iter().enumerate() {
+            match operation {
+                Operation::CreateUser(user) => {
+                    query = query
+                        .bind((format!("id_{i}"), user.id.clone()))
+                        .bind((format!("user_{i}"), NewUserRecord::from(user)));
+                }
+                Operation::DeleteUser { id, precondition } => {
+                    let versions = expected_versions(precondition.clone());
+                    query = query
+                        .bind((format!("id_{i}"), id.clone()))
+                        .bind((format!("versions_{i}"), versions));
+                }
+            }
+        }
+
+        let mut response = query
+            .await
+            .map_err(|e| query_error(e, UserRepositoryError::DatabaseError))?;
+
+        match transaction::failure(&mut response) {
+            Some(reason) if violates_email_index(&reason) => Err(UserRepositoryError::EmailTaken),
+            Some(reason) if reason.ends_with(NO_SUCH_USER) => Err(UserRepositoryError::NotFound),
+            Some(reason) if reason.ends_with(STALE_USER) => {
+                Err(UserRepositoryError::VersionMismatch)
+            }
+            Some(reason) => Err(UserRepositoryError::DatabaseError(reason)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use surrealdb::engine::any::connect;
+
+    use super::*;
+    use crate::infra::db::{conformance, migrations::Migrator};
+
+    async fn repository() -> SurrealUserRepository {
+        let client = connect("mem://").await.unwrap();
+        client.use_ns("test").use_db("test").await.unwrap();
+        Migrator::load("migrations")
+            .unwrap()
+            .migrate(&client)
+            .await
+            .unwrap();
+
+        SurrealUserRepository::new(Arc::new(client))
+    }
+
+    #[tokio::test]
+    async fn lookups_bind_their_arguments() {
+        let repo = repository().await;
+        let user = repo
+            .create(NewUser {
+                username: "ana".to_string(),
+                email: "ana@example.com".to_string(),
+                password: "hash".to_string(),
+                roles: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let by_id = repo.get_by_id(user.id.clone()).await.unwrap();
+        assert_eq!(by_id.map(|u| u.email), Some(user.email.clone()));
+        let by_email = repo.get_by_email(&user.email).await.unwrap();
+        assert_eq!(by_email.map(|u| u.id), Some(user.id));
+
+        let injected = repo.get_by_email("' OR true OR email = '").await.unwrap();
+        assert!(injected.is_none());
+        let injected = repo.get_by_id("x; REMOVE TABLE users".to_string()).await;
+        assert!(injected.unwrap().is_none());
+        assert!(repo.get_by_email(&user.email).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn pages_past_any_start_surrealdb_takes_are_empty() {
+        let repo = repository().await;
+        let spec = PageConfig {
+            page: Some(u32::MAX),
+            per_page: Some(100),
+            created_after: None,
+            created_before: None,
+            updated_after: None,
+            updated_before: None,
+            last_login_after: None,
+            last_login_before: None,
+            sort: None,
+            order: None,
+        };
+
+        assert!(repo.list(spec).await.unwrap().is_empty());
+    }
 
-        Ok(users)
+    #[tokio::test]
+    async fn conforms() {
+        conformance::check(repository).await;
     }
 }
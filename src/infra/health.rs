@@ -0,0 +1,305 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde::{Deserialize, Serialize};
+use sqlx::AnyPool;
+use surrealdb::{Surreal, engine::any::Any};
+
+use crate::config::settings::Settings;
+
+/// How long the database may take to answer a readiness ping.
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentHealth {
+    pub status: ComponentStatus,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReadinessStatus {
+    Ready,
+    NotReady,
+    ShuttingDown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: ReadinessStatus,
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+/// The store behind the user repository, as far as readiness is concerned.
+pub enum Database {
+    SurrealDb(Arc<Surreal<Any>>),
+    Sql(AnyPool),
+    /// Always up.
+    Memory,
+}
+
+impl Database {
+    async fn ping(&self) -> Result<(), String> {
+        match self {
+            Database::SurrealDb(client) => {
+                let response = client
+                    .query("RETURN true")
+                    .await
+                    .map_err(|e| e.to_string())?;
+                response.check().map(|_| ()).map_err(|e| e.to_string())
+            }
+            Database::Sql(pool) => sqlx::query("SELECT 1")
+                .execute(pool)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Database::Memory => Ok(()),
+        }
+    }
+}
+
+/// Checks whether the service can take traffic: the database answers, the
+/// configuration is usable and the signing key works.
+pub struct HealthCheck {
+    database: Database,
+    jwt_secret: String,
+    configuration_error: Option<String>,
+    shutting_down: AtomicBool,
+}
+
+impl HealthCheck {
+    pub fn new(database: Database, settings: &Settings) -> Self {
+        Self {
+            database,
+            jwt_secret: settings.jwt.secret.clone(),
+            configuration_error: configuration_error(settings),
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    /// Marks the service as draining; readiness fails from then on.
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Relaxed);
+    }
+
+    pub async fn readiness(&self) -> Readiness {
+        let mut components = BTreeMap::new();
+        components.insert("database", self.check_database().await);
+        components.insert(
+            "configuration",
+            timed(|| self.configuration_error.clone().map_or(Ok(()), Err)),
+        );
+        components.insert("signing_key", timed(|| self.check_signing_key()));
+
+        let status = if self.shutting_down.load(Ordering::Relaxed) {
+            ReadinessStatus::ShuttingDown
+        } else if components
+            .values()
+            .all(|component| component.status == ComponentStatus::Up)
+        {
+            ReadinessStatus::Ready
+        } else {
+            ReadinessStatus::NotReady
+        };
+
+        Readiness { status, components }
+    }
+
+    async fn check_database(&self) -> ComponentHealth {
+        let start = Instant::now();
+        let outcome = match tokio::time::timeout(DATABASE_TIMEOUT, self.database.ping()).await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(format!(
+                "No answer within {}ms",
+                DATABASE_TIMEOUT.as_millis()
+            )),
+        };
+
+        component(outcome, start)
+    }
+
+    fn check_signing_key(&self) -> Result<(), String> {
+        if self.jwt_secret.is_empty() {
+            return Err("JWT secret is empty".to_string());
+        }
+
+        let probe = Probe {
+            exp: Utc::now().timestamp() + 60,
+        };
+        let token = encode(
+            &Header::default(),
+            &probe,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )
+        .map_err(|e| e.to_string())?;
+
+        decode::<Probe>(
+            &token,
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Claims signed and verified to prove the JWT key is usable.
+#[derive(Serialize, Deserialize)]
+struct Probe {
+    exp: i64,
+}
+
+fn configuration_error(settings: &Settings) -> Option<String> {
+    if settings.jwt.expiration <= 0 {
+        return Some("jwt.expiration must be positive".to_string());
+    }
+    if settings.server.admin_port == Some(settings.server.port) {
+        return Some("server.admin_port must differ from server.port".to_string());
+    }
+    None
+}
+
+fn timed(check: impl FnOnce() -> Result<(), String>) -> ComponentHealth {
+    let start = Instant::now();
+    component(check(), start)
+}
+
+fn component(outcome: Result<(), String>, start: Instant) -> ComponentHealth {
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    match outcome {
+        Ok(()) => ComponentHealth {
+            status: ComponentStatus::Up,
+            latency_ms,
+            error: None,
+        },
+        Err(error) => ComponentHealth {
+            status: ComponentStatus::Down,
+            latency_ms,
+            error: Some(error),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::any::{AnyPoolOptions, install_default_drivers};
+
+    use super::*;
+
+    fn settings() -> Settings {
+        serde_json::from_value(json!({
+            "server": {
+                "port": 8000,
+                "address": "127.0.0.1",
+                "allowed_origins": [],
+                "allowed_methods": ["GET"],
+            },
+            "jwt": { "secret": "test-secret", "expiration": 3600 },
+        }))
+        .unwrap()
+    }
+
+    /// A single-connection pool, so holding its connection starves pings.
+    async fn sqlite_pool() -> AnyPool {
+        install_default_drivers();
+        AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn ready_while_every_component_is_up() {
+        let health_check = HealthCheck::new(Database::Sql(sqlite_pool().await), &settings());
+
+        let readiness = health_check.readiness().await;
+        assert_eq!(readiness.status, ReadinessStatus::Ready);
+        for (name, component) in &readiness.components {
+            assert_eq!(component.status, ComponentStatus::Up, "{name}");
+            assert!(component.error.is_none(), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn not_ready_while_the_database_is_down() {
+        let pool = sqlite_pool().await;
+        pool.close().await;
+        let health_check = HealthCheck::new(Database::Sql(pool), &settings());
+
+        let readiness = health_check.readiness().await;
+        assert_eq!(readiness.status, ReadinessStatus::NotReady);
+        let database = &readiness.components["database"];
+        assert_eq!(database.status, ComponentStatus::Down);
+        assert!(database.error.is_some());
+        assert_eq!(
+            readiness.components["signing_key"].status,
+            ComponentStatus::Up
+        );
+    }
+
+    #[tokio::test]
+    async fn not_ready_while_the_database_does_not_answer() {
+        let pool = sqlite_pool().await;
+        let _busy = pool.acquire().await.unwrap();
+        let health_check = HealthCheck::new(Database::Sql(pool.clone()), &settings());
+
+        let readiness = health_check.readiness().await;
+        assert_eq!(readiness.status, ReadinessStatus::NotReady);
+        let database = &readiness.components["database"];
+        assert_eq!(database.status, ComponentStatus::Down);
+        assert_eq!(database.error.as_deref(), Some("No answer within 2000ms"));
+    }
+
+    #[tokio::test]
+    async fn not_ready_with_an_unusable_configuration() {
+        let mut settings = settings();
+        settings.jwt.secret = String::new();
+        settings.server.admin_port = Some(settings.server.port);
+        let health_check = HealthCheck::new(Database::Memory, &settings);
+
+        let readiness = health_check.readiness().await;
+        assert_eq!(readiness.status, ReadinessStatus::NotReady);
+        assert_eq!(readiness.components["database"].status, ComponentStatus::Up);
+        for name in ["configuration", "signing_key"] {
+            assert_eq!(
+                readiness.components[name].status,
+                ComponentStatus::Down,
+                "{name}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn shutting_down_while_otherwise_ready() {
+        let health_check = HealthCheck::new(Database::Memory, &settings());
+        assert_eq!(
+            health_check.readiness().await.status,
+            ReadinessStatus::Ready
+        );
+
+        health_check.begin_shutdown();
+        assert_eq!(
+            health_check.readiness().await.status,
+            ReadinessStatus::ShuttingDown
+        );
+    }
+}
@@ -4,4 +4,5 @@ pub mod auth;
 pub mod config;
 pub mod core;
 pub mod infra;
+pub mod metrics;
 pub mod telemetry;
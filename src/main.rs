@@ -1,14 +1,46 @@
 #![allow(clippy::result_large_err)]
-use api::{app::build_app, telemetry};
+use api::{
+    app::{ApplicationError, build_app},
+    config::{
+        load_settings,
+        settings::{DatabaseBackend, Settings},
+    },
+    infra::db::{
+        connection::create_surreal_client,
+        migrations::Migrator,
+        sql_connection::{create_sql_pool, pending_sql_migrations, run_sql_migrations},
+    },
+    telemetry,
+};
 
 extern crate rocket;
 
 #[rocket::main]
 async fn main() -> Result<(), rocket::Error> {
     dotenvy::dotenv().expect("Failed to load .env file");
-    let guard = telemetry::init();
+    let cfg = load_settings()
+        .map_err(ApplicationError::ConfigurationParsing)
+        .map_err(|e| panic!("{}", e))
+        .unwrap();
+    let guard = telemetry::init(&cfg.telemetry)
+        .map_err(|e| panic!("{}", e))
+        .unwrap();
 
-    build_app()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("migrate") => {
+            let dry_run = args.iter().any(|arg| arg == "--dry-run");
+            migrate(&cfg, dry_run)
+                .await
+                .map_err(|e| panic!("{}", e))
+                .unwrap();
+            return Ok(());
+        }
+        Some(command) => panic!("Unknown command `{command}`, expected `migrate [--dry-run]`"),
+        None => {}
+    }
+
+    build_app(cfg)
         .await
         .map_err(|e| panic!("{}", e))
         .unwrap()
@@ -19,3 +51,52 @@ async fn main() -> Result<(), rocket::Error> {
 
     Ok(())
 }
+
+/// Applies pending migrations for the configured backend, or with
+/// `--dry-run` prints them without applying them.
+async fn migrate(cfg: &Settings, dry_run: bool) -> Result<(), ApplicationError> {
+    let pending: Vec<(String, String)> = match cfg.database.backend {
+        DatabaseBackend::SurrealDb => {
+            let client = create_surreal_client(&cfg.surrealdb).await?;
+            let migrator = Migrator::load(&cfg.surrealdb.migrations.directory)?;
+            let migrations = if dry_run {
+                migrator.pending(&client).await?
+            } else {
+                migrator.migrate(&client).await?
+            };
+            migrations
+                .into_iter()
+                .map(|m| (format!("{:04}_{}", m.version, m.name), m.script.clone()))
+                .collect()
+        }
+        DatabaseBackend::Sqlite | DatabaseBackend::Postgres => {
+            let pool = create_sql_pool(cfg.database.backend, &cfg.database.sql).await?;
+            let migrations = if dry_run {
+                pending_sql_migrations(&pool).await?
+            } else {
+                run_sql_migrations(&pool).await?
+            };
+            migrations
+                .into_iter()
+                .map(|m| {
+                    (
+                        format!("{:04}_{}", m.version, m.description),
+                        m.sql.to_string(),
+                    )
+                })
+                .collect()
+        }
+        DatabaseBackend::Memory => Vec::new(),
+    };
+
+    if dry_run {
+        for (name, script) in &pending {
+            println!("-- {name}\n{script}");
+        }
+        println!("{} pending migration(s)", pending.len());
+    } else {
+        println!("Applied {} migration(s)", pending.len());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use std::sync::LazyLock;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use rocket::{
+    Data, Request, Response,
+    fairing::{Fairing, Info, Kind},
+    get,
+    http::ContentType,
+};
+
+use crate::api::request_id::RequestStart;
+
+/// Process-wide metrics, registered in their own registry.
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub logins: IntCounterVec,
+    pub password_hash_duration: HistogramVec,
+    pub repository_query_duration: HistogramVec,
+    pub user_cache_lookups: IntCounterVec,
+    pub token_validations: IntCounterVec,
+    pub active_token_validations: IntGauge,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests served"),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time spent serving HTTP requests",
+            ),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+
+        let logins = IntCounterVec::new(
+            Opts::new("logins_total", "Login attempts by outcome"),
+            &["outcome"],
+        )
+        .expect("valid metric");
+
+        let password_hash_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "password_hash_duration_seconds",
+                "Time spent hashing or verifying passwords with Argon2",
+            )
+            .buckets(vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]),
+            &["operation"],
+        )
+        .expect("valid metric");
+
+        let repository_query_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "repository_query_duration_seconds",
+                "Latency of user repository calls",
+            ),
+            &["method"],
+        )
+        .expect("valid metric");
+
+        let user_cache_lookups = IntCounterVec::new(
+            Opts::new("user_cache_lookups_total", "User cache lookups by outcome"),
+            &["outcome"],
+        )
+        .expect("valid metric");
+
+        let token_validations = IntCounterVec::new(
+            Opts::new("token_validations_total", "JWT validations by outcome"),
+            &["outcome"],
+        )
+        .expect("valid metric");
+
+        let active_token_validations = IntGauge::new(
+            "token_validations_active",
+            "JWT validations currently in progress",
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_request_duration.clone()),
+            Box::new(logins.clone()),
+            Box::new(password_hash_duration.clone()),
+            Box::new(repository_query_duration.clone()),
+            Box::new(user_cache_lookups.clone()),
+            Box::new(token_validations.clone()),
+            Box::new(active_token_validations.clone()),
+        ] {
+            registry.register(collector).expect("metric registered once");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            logins,
+            password_hash_duration,
+            repository_query_duration,
+            user_cache_lookups,
+            token_validations,
+            active_token_validations,
+        }
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("Failed to encode metrics: {e}");
+        }
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Holds a gauge up by one until dropped, so work that is cancelled or
+/// panics cannot leave the gauge counting it.
+pub struct InFlight(IntGauge);
+
+impl InFlight {
+    pub fn track(gauge: &IntGauge) -> Self {
+        gauge.inc();
+        Self(gauge.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Records request counts and latencies per matched route and status.
+pub struct HttpMetrics;
+
+#[rocket::async_trait]
+impl Fairing for HttpMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "HTTP metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestStart::record(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        // Unmatched paths share one label to keep cardinality bounded.
+        let route = request
+            .route()
+            .map(|route| route.uri.to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let method = request.method().as_str();
+        let status = response.status().code.to_string();
+        let labels = [method, route.as_str(), status.as_str()];
+
+        metrics().http_requests.with_label_values(&labels).inc();
+
+        if let Some(elapsed) = RequestStart::elapsed(request) {
+            metrics()
+                .http_request_duration
+                .with_label_values(&labels)
+                .observe(elapsed.as_secs_f64());
+        }
+    }
+}
+
+#[get("/metrics")]
+pub fn metrics_endpoint() -> (ContentType, String) {
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        metrics().render(),
+    )
+}
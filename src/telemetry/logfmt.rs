@@ -0,0 +1,181 @@
+use std::fmt::{self, Write};
+
+use chrono::{SecondsFormat, Utc};
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{
+    field::RecordFields,
+    fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, format::Writer},
+    registry::LookupSpan,
+};
+
+/// Formats events as `key=value` pairs, preceded by the fields of every
+/// enclosing span.
+pub struct Logfmt;
+
+impl<S, N> FormatEvent<S, N> for Logfmt
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        write!(
+            writer,
+            "ts={} level={} target={}",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            metadata.level().as_str().to_ascii_lowercase(),
+            metadata.target(),
+        )?;
+
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<FormattedFields<N>>() {
+                    write!(writer, "{fields}")?;
+                }
+            }
+        }
+
+        ctx.format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// Field formatter writing each field as ` key=value`.
+pub struct LogfmtFields;
+
+impl<'writer> FormatFields<'writer> for LogfmtFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut visitor = LogfmtVisitor {
+            writer,
+            result: Ok(()),
+        };
+        fields.record(&mut visitor);
+        visitor.result
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        self.format_fields(current.as_writer(), fields)
+    }
+}
+
+struct LogfmtVisitor<'writer> {
+    writer: Writer<'writer>,
+    result: fmt::Result,
+}
+
+impl LogfmtVisitor<'_> {
+    fn pair(&mut self, field: &Field, value: &str) {
+        if self.result.is_err() {
+            return;
+        }
+
+        let key = match field.name() {
+            "message" => "msg",
+            name => name,
+        };
+
+        self.result = if needs_quoting(value) {
+            write!(self.writer, " {key}={value:?}")
+        } else {
+            write!(self.writer, " {key}={value}")
+        };
+    }
+}
+
+impl Visit for LogfmtVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.pair(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let mut formatted = String::new();
+        let _ = write!(formatted, "{value:?}");
+        self.pair(field, &formatted);
+    }
+}
+
+fn needs_quoting(value: &str) -> bool {
+    value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control())
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::{info, info_span};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::telemetry::tests::Buffer;
+
+    fn logged(log: impl FnOnce()) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let layer = tracing_subscriber::fmt::layer()
+            .event_format(Logfmt)
+            .fmt_fields(LogfmtFields)
+            .with_writer(move || writer.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, log);
+        buffer.take()
+    }
+
+    #[test]
+    fn quotes_and_escapes_values_that_need_it() {
+        let line = logged(|| {
+            info!(
+                plain = "word",
+                spaced = "two words",
+                quoted = r#"say "hi""#,
+                assignment = "a=b",
+                path = r"C:\logs",
+                empty = "",
+                count = 3,
+                "Logged in"
+            );
+        });
+
+        for pair in [
+            " plain=word",
+            r#" spaced="two words""#,
+            r#" quoted="say \"hi\"""#,
+            r#" assignment="a=b""#,
+            r#" path="C:\\logs""#,
+            r#" empty="""#,
+            " count=3",
+            r#" msg="Logged in""#,
+        ] {
+            assert!(line.contains(pair), "{pair} missing from {line}");
+        }
+        assert!(line.starts_with("ts="), "{line}");
+        assert!(line.contains(" level=info "), "{line}");
+        assert!(line.ends_with('\n'));
+    }
+
+    #[test]
+    fn prefixes_the_fields_of_enclosing_spans() {
+        let line = logged(|| {
+            let span = info_span!("request", request_id = "req 1", method = "GET");
+            let _entered = span.enter();
+            info!(status = 200, "done");
+        });
+
+        let span_fields = line.find(r#" request_id="req 1" method=GET"#).unwrap();
+        let event_fields = line.find(" msg=done status=200").unwrap();
+        assert!(span_fields < event_fields, "{line}");
+    }
+}
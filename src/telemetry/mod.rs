@@ -0,0 +1,435 @@
+mod logfmt;
+mod redaction;
+mod size_rolling;
+
+use std::{collections::HashMap, io::Write, sync::OnceLock};
+
+use opentelemetry::{
+    Context, global,
+    propagation::{Extractor, TextMapCompositePropagator},
+    trace::TracerProvider as _,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource,
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
+};
+use rocket::http::HeaderMap;
+use thiserror::Error;
+use tracing::{Span, Subscriber};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry,
+    fmt::MakeWriter,
+    layer::{Layered, SubscriberExt},
+    registry::LookupSpan,
+    reload,
+    util::SubscriberInitExt,
+};
+
+use crate::config::settings::{
+    LogFileSettings, LogFormat, LogOutput, LogRotation, OtlpSettings, RedactionSettings,
+    TelemetrySettings,
+};
+use logfmt::{Logfmt, LogfmtFields};
+use redaction::{Redact, RedactionPolicy};
+use size_rolling::SizeRollingAppender;
+
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+const TRACESTATE_HEADER: &str = "tracestate";
+
+type Filtered = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+type BoxedLayer = Box<dyn Layer<Filtered> + Send + Sync>;
+
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+#[derive(Debug, Error)]
+pub enum TelemetryError {
+    #[error("Invalid log filter: {0}")]
+    Filter(String),
+
+    #[error("Failed to open log file: {0}")]
+    LogFile(String),
+}
+
+/// Keeps log writers and trace exporters alive; dropping it flushes them.
+pub struct TelemetryGuard {
+    _file_writer: Option<WorkerGuard>,
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush traces: {e}");
+        }
+    }
+}
+
+/// Handle on the installed log filter, used to change it at runtime.
+pub struct LogFilter(reload::Handle<EnvFilter, Registry>);
+
+impl LogFilter {
+    pub fn current(&self) -> String {
+        self.0
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the filter with `directives`, given in `RUST_LOG` syntax.
+    pub fn set(&self, directives: &str) -> Result<(), TelemetryError> {
+        let filter = parse_filter(directives)?;
+        self.0
+            .reload(filter)
+            .map_err(|e| TelemetryError::Filter(e.to_string()))
+    }
+}
+
+/// The log filter installed by [`init`], if telemetry was initialised.
+pub fn log_filter() -> Option<&'static LogFilter> {
+    LOG_FILTER.get()
+}
+
+/// Installs an `info` log filter for tests of the admin endpoint, which run
+/// without [`init`]. The filter drives a subscriber nothing logs to.
+#[cfg(test)]
+pub(crate) fn test_log_filter() -> &'static LogFilter {
+    LOG_FILTER.get_or_init(|| {
+        let (filter, handle) = reload::Layer::new(EnvFilter::new("info"));
+        // The handle only reloads while its layer is alive.
+        Box::leak(Box::new(tracing_subscriber::registry().with(filter)));
+        LogFilter(handle)
+    })
+}
+
+pub fn init(settings: &TelemetrySettings) -> Result<TelemetryGuard, TelemetryError> {
+    let (filter, filter_handle) = reload::Layer::new(parse_filter(&filter_directives(settings))?);
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+
+    if settings.output != LogOutput::File {
+        layers.push(fmt_layer(settings.console_format, std::io::stdout, true));
+    }
+
+    let file_writer = match settings.output {
+        LogOutput::Stdout => None,
+        LogOutput::File | LogOutput::Both => {
+            let (writer, guard) = tracing_appender::non_blocking(file_appender(&settings.file)?);
+            layers.push(fmt_layer(settings.file.format, writer, false));
+            Some(guard)
+        }
+    };
+
+    let tracer_provider = settings.otlp.as_ref().and_then(|otlp| {
+        otlp_provider(otlp)
+            .inspect_err(|e| eprintln!("OTLP export disabled: {e}"))
+            .ok()
+    });
+
+    if let Some(provider) = &tracer_provider {
+        layers.push(trace_layer(provider));
+    }
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(redacting(layers, &settings.redaction))
+        .init();
+
+    let _ = LOG_FILTER.set(LogFilter(filter_handle));
+
+    Ok(TelemetryGuard {
+        _file_writer: file_writer,
+        tracer_provider,
+    })
+}
+
+/// Puts `layers` behind redaction, unless it is disabled.
+fn redacting(layers: Vec<BoxedLayer>, settings: &RedactionSettings) -> BoxedLayer {
+    if settings.enabled {
+        Redact::new(layers, RedactionPolicy::new(settings)).boxed()
+    } else {
+        layers.boxed()
+    }
+}
+
+/// `RUST_LOG` when set, otherwise the configured level and directives.
+fn filter_directives(settings: &TelemetrySettings) -> String {
+    std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|directives| !directives.trim().is_empty())
+        .unwrap_or_else(|| {
+            std::iter::once(settings.level.as_str())
+                .chain(settings.directives.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+}
+
+fn parse_filter(directives: &str) -> Result<EnvFilter, TelemetryError> {
+    EnvFilter::builder()
+        .parse(directives)
+        .map_err(|e| TelemetryError::Filter(e.to_string()))
+}
+
+fn fmt_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+
+    match format {
+        LogFormat::Json => layer.json().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Logfmt => layer.event_format(Logfmt).fmt_fields(LogfmtFields).boxed(),
+    }
+}
+
+fn file_appender(settings: &LogFileSettings) -> Result<Box<dyn Write + Send>, TelemetryError> {
+    let rotation = match settings.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Size => {
+            return SizeRollingAppender::new(
+                &settings.directory,
+                &settings.file_name,
+                settings.max_size_mb.saturating_mul(1024 * 1024),
+                settings.max_files,
+            )
+            .map(|appender| Box::new(appender) as Box<dyn Write + Send>)
+            .map_err(|e| TelemetryError::LogFile(e.to_string()));
+        }
+    };
+
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(&settings.file_name);
+    if let Some(max_files) = settings.max_files {
+        builder = builder.max_log_files(max_files.max(1));
+    }
+
+    builder
+        .build(&settings.directory)
+        .map(|appender| Box::new(appender) as Box<dyn Write + Send>)
+        .map_err(|e| TelemetryError::LogFile(e.to_string()))
+}
+
+fn otlp_provider(
+    settings: &OtlpSettings,
+) -> Result<SdkTracerProvider, opentelemetry_otlp::ExporterBuildError> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(settings.endpoint.clone())
+        .build()?;
+
+    Ok(tracer_provider(exporter, settings))
+}
+
+/// Batches spans to `exporter`, and installs the provider and W3C trace
+/// context propagation globally.
+fn tracer_provider(
+    exporter: impl opentelemetry_sdk::trace::SpanExporter + 'static,
+    settings: &OtlpSettings,
+) -> SdkTracerProvider {
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            settings.sample_ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(settings.service_name.clone())
+                .build(),
+        )
+        .build();
+
+    global::set_tracer_provider(provider.clone());
+    global::set_text_map_propagator(TextMapCompositePropagator::new(vec![Box::new(
+        TraceContextPropagator::new(),
+    )]));
+
+    provider
+}
+
+/// Hands spans to `provider` for export.
+fn trace_layer<S>(provider: &SdkTracerProvider) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+        .boxed()
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap<'a>);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get_one(key)
+    }
+
+    /// Only the W3C trace context headers are ever propagated.
+    fn keys(&self) -> Vec<&str> {
+        [TRACEPARENT_HEADER, TRACESTATE_HEADER]
+            .into_iter()
+            .filter(|key| self.0.contains(*key))
+            .collect()
+    }
+}
+
+/// Trace context sent by the caller in `traceparent`/`tracestate`, if any.
+pub fn remote_context(headers: &HeaderMap<'_>) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// `traceparent` value identifying `span`, when spans are being exported.
+pub fn traceparent(span: &Span) -> Option<String> {
+    let mut carrier = HashMap::new();
+    let context = span.context();
+
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+
+    carrier.remove(TRACEPARENT_HEADER)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use opentelemetry::trace::{SpanId, TraceId};
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use rocket::{get, http::Header, local::blocking::Client};
+    use tracing::{debug, info, instrument};
+
+    use super::*;
+    use crate::api::request_id::{RequestIds, traced};
+
+    /// Collects everything written to it, for asserting on log output.
+    #[derive(Clone, Default)]
+    pub(super) struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        /// Everything written so far, leaving the buffer empty.
+        pub(super) fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    #[instrument]
+    fn lookup() {}
+
+    #[get("/traced")]
+    fn traced_route() {
+        lookup();
+    }
+
+    #[test]
+    fn exports_request_spans_joined_to_the_callers_trace() {
+        let exporter = InMemorySpanExporter::default();
+        let settings = OtlpSettings {
+            endpoint: String::new(),
+            service_name: "test".to_string(),
+            // Exported only because the caller sampled the trace.
+            sample_ratio: 0.0,
+        };
+        let provider = tracer_provider(exporter.clone(), &settings);
+        let subscriber = tracing_subscriber::registry().with(trace_layer(&provider));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let rocket = rocket::build()
+            .mount("/", traced(rocket::routes![traced_route]))
+            .attach(RequestIds);
+        let client = Client::tracked(rocket).unwrap();
+        let echoed = client
+            .get("/traced")
+            .header(Header::new(
+                TRACEPARENT_HEADER,
+                format!("00-{TRACE_ID}-{PARENT_ID}-01"),
+            ))
+            .dispatch()
+            .headers()
+            .get_one(TRACEPARENT_HEADER)
+            .map(str::to_string)
+            .unwrap();
+        assert!(echoed.starts_with(&format!("00-{TRACE_ID}-")));
+
+        provider.force_flush().unwrap();
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = |name: &str| {
+            spans
+                .iter()
+                .find(|span| span.name == name)
+                .unwrap_or_else(|| panic!("no `{name}` span exported"))
+        };
+
+        let request = span("request");
+        let trace_id = TraceId::from_hex(TRACE_ID).unwrap();
+        assert_eq!(request.span_context.trace_id(), trace_id);
+        assert_eq!(request.parent_span_id, SpanId::from_hex(PARENT_ID).unwrap());
+        assert!(echoed.contains(&request.span_context.span_id().to_string()));
+
+        let lookup = span("lookup");
+        assert_eq!(lookup.span_context.trace_id(), trace_id);
+        assert_eq!(lookup.parent_span_id, request.span_context.span_id());
+    }
+
+    #[test]
+    fn filter_changes_take_effect_and_invalid_ones_are_rejected() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let (filter, handle) = reload::Layer::new(parse_filter("info").unwrap());
+        let subscriber = tracing_subscriber::registry().with(filter).with(fmt_layer(
+            LogFormat::Logfmt,
+            move || writer.clone(),
+            false,
+        ));
+        let log_filter = LogFilter(handle);
+
+        tracing::subscriber::with_default(subscriber, || {
+            debug!("before");
+            info!("kept");
+            let output = buffer.take();
+            assert!(output.contains("msg=kept") && !output.contains("before"));
+
+            log_filter.set("debug").unwrap();
+            debug!("after");
+            assert!(buffer.take().contains("msg=after"));
+
+            log_filter.set("warn").unwrap();
+            info!("quiet");
+            assert_eq!(buffer.take(), "");
+
+            let rejected = log_filter.set("debug,api=loud");
+            assert!(matches!(rejected, Err(TelemetryError::Filter(_))));
+            assert_eq!(log_filter.current(), "warn");
+            info!("still quiet");
+            assert_eq!(buffer.take(), "");
+        });
+    }
+}
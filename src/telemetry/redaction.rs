@@ -0,0 +1,524 @@
+use std::{
+    any::TypeId,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::Once,
+};
+
+use sha2::{Digest, Sha256};
+use tracing::{
+    Dispatch, Event, Metadata, Subscriber,
+    field::{DisplayValue, Field, FieldSet, Value, ValueSet, Visit, display},
+    span::{Attributes, Id, Record},
+    subscriber::Interest,
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::config::settings::{RedactionMode, RedactionSettings};
+
+const MASK: &str = "[REDACTED]";
+
+/// Most fields a callsite can declare.
+const MAX_FIELDS: usize = 32;
+
+/// Decides which values are sensitive and what replaces them.
+pub struct RedactionPolicy {
+    mode: RedactionMode,
+    fields: Vec<String>,
+    hash_key: Vec<u8>,
+}
+
+impl RedactionPolicy {
+    pub fn new(settings: &RedactionSettings) -> Self {
+        Self {
+            mode: settings.mode,
+            fields: settings
+                .fields
+                .iter()
+                .map(|field| field.to_ascii_lowercase())
+                .collect(),
+            hash_key: settings.hash_key.clone().unwrap_or_default().into_bytes(),
+        }
+    }
+
+    fn is_sensitive(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        self.fields.iter().any(|field| {
+            name.strip_suffix(field.as_str())
+                .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with(['_', '.']))
+        })
+    }
+
+    fn replacement(&self, value: &str) -> String {
+        match self.mode {
+            RedactionMode::Mask => MASK.to_string(),
+            RedactionMode::Hash => {
+                let digest = Sha256::new()
+                    .chain_update(&self.hash_key)
+                    .chain_update(value)
+                    .finalize();
+                let hex: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+                format!("sha256:{hex}")
+            }
+        }
+    }
+
+    /// Redacts sensitive values inside free text, such as messages and the
+    /// `Debug` output of a request struct recorded as a whole.
+    fn scrub(&self, text: &str) -> Option<String> {
+        let pairs = self.scrub_pairs(text);
+        let words = self.scrub_words(pairs.as_deref().unwrap_or(text));
+        words.or(pairs)
+    }
+
+    /// Redacts the values of sensitive `key: value` and `key=value` pairs.
+    fn scrub_pairs(&self, text: &str) -> Option<String> {
+        let mut scrubbed = String::with_capacity(text.len());
+        let mut rest = text;
+        let mut changed = false;
+
+        while let Some((position, separator)) = next_separator(rest) {
+            let (before, after) = rest.split_at(position);
+            let key_start = before
+                .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(0, |i| i + 1);
+            let key = &before[key_start..];
+
+            scrubbed.push_str(before);
+            scrubbed.push_str(separator);
+            rest = &after[separator.len()..];
+
+            if key.is_empty() || !self.is_sensitive(key) || rest.starts_with("None") {
+                continue;
+            }
+
+            let (value, remainder) = split_debug_value(rest);
+            scrubbed.push_str(&self.replacement(value));
+            rest = remainder;
+            changed = true;
+        }
+
+        scrubbed.push_str(rest);
+        changed.then_some(scrubbed)
+    }
+
+    /// Redacts email and IP addresses written without a key, when `email`
+    /// or `ip` is a sensitive field.
+    fn scrub_words(&self, text: &str) -> Option<String> {
+        let emails = self.is_sensitive("email");
+        let ips = self.is_sensitive("ip");
+        if !emails && !ips {
+            return None;
+        }
+
+        let mut scrubbed = String::with_capacity(text.len());
+        let mut changed = false;
+
+        for piece in text.split_inclusive(is_word_boundary) {
+            let word = piece.trim_end_matches(is_word_boundary);
+            let word = word.trim_end_matches(['.', ':']);
+
+            if (emails && is_email(word)) || (ips && is_ip(word)) {
+                scrubbed.push_str(&self.replacement(word));
+                scrubbed.push_str(&piece[word.len()..]);
+                changed = true;
+            } else {
+                scrubbed.push_str(piece);
+            }
+        }
+
+        changed.then_some(scrubbed)
+    }
+}
+
+/// The first `: ` or `=` in `text`, with its position.
+fn next_separator(text: &str) -> Option<(usize, &'static str)> {
+    [": ", "="]
+        .into_iter()
+        .filter_map(|separator| text.find(separator).map(|i| (i, separator)))
+        .min()
+}
+
+fn is_word_boundary(c: char) -> bool {
+    c.is_whitespace() || ",;()[]{}<>\"'=".contains(c)
+}
+
+fn is_email(word: &str) -> bool {
+    word.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty() && !domain.contains('@') && domain.trim_start_matches('.').contains('.')
+    })
+}
+
+fn is_ip(word: &str) -> bool {
+    word.parse::<IpAddr>().is_ok() || word.parse::<SocketAddr>().is_ok()
+}
+
+/// Splits `text` after the `Debug`-formatted value it starts with, returning
+/// the value without `Some(..)` or quotes, and what follows it.
+fn split_debug_value(text: &str) -> (&str, &str) {
+    let inner = text.strip_prefix("Some(");
+    let body = inner.unwrap_or(text);
+
+    let (value, mut consumed) = match body.strip_prefix('"') {
+        Some(quoted) => {
+            let mut escaped = false;
+            let end = quoted
+                .char_indices()
+                .find(|&(_, c)| {
+                    let closes = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    closes
+                })
+                .map_or(quoted.len(), |(i, _)| i);
+            (&quoted[..end], (end + 2).min(body.len()))
+        }
+        None => {
+            let end = body.find([',', ')', '}', ' ']).unwrap_or(body.len());
+            (&body[..end], end)
+        }
+    };
+
+    consumed += text.len() - body.len();
+    if inner.is_some() && text[consumed..].starts_with(')') {
+        consumed += 1;
+    }
+
+    (value, &text[consumed..])
+}
+
+/// Value copied out of a field so it can be recorded again after redaction.
+enum OwnedValue {
+    Str(String),
+    Formatted(DisplayValue<String>),
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+    F64(f64),
+    Bool(bool),
+}
+
+impl OwnedValue {
+    fn as_value(&self) -> &dyn Value {
+        match self {
+            OwnedValue::Str(value) => value,
+            OwnedValue::Formatted(value) => value,
+            OwnedValue::I64(value) => value,
+            OwnedValue::U64(value) => value,
+            OwnedValue::I128(value) => value,
+            OwnedValue::U128(value) => value,
+            OwnedValue::F64(value) => value,
+            OwnedValue::Bool(value) => value,
+        }
+    }
+}
+
+/// Copies every field, redacting sensitive ones along the way.
+struct Collector<'p> {
+    policy: &'p RedactionPolicy,
+    values: Vec<(Field, OwnedValue)>,
+    redacted: bool,
+}
+
+impl<'p> Collector<'p> {
+    fn new(policy: &'p RedactionPolicy) -> Self {
+        Self {
+            policy,
+            values: Vec::new(),
+            redacted: false,
+        }
+    }
+
+    fn push(&mut self, field: &Field, value: OwnedValue) {
+        self.values.push((field.clone(), value));
+    }
+
+    fn push_text(&mut self, field: &Field, text: String, quoted: bool) {
+        let text = if self.policy.is_sensitive(field.name()) {
+            self.redacted = true;
+            self.policy.replacement(text.trim_matches('"'))
+        } else if let Some(scrubbed) = self.policy.scrub(&text) {
+            self.redacted = true;
+            scrubbed
+        } else {
+            text
+        };
+
+        let value = if quoted {
+            OwnedValue::Str(text)
+        } else {
+            OwnedValue::Formatted(display(text))
+        };
+        self.push(field, value);
+    }
+
+    fn push_number(&mut self, field: &Field, value: OwnedValue, text: impl FnOnce() -> String) {
+        if self.policy.is_sensitive(field.name()) {
+            self.push_text(field, text(), true);
+        } else {
+            self.push(field, value);
+        }
+    }
+
+    /// The copied values, or `None` when nothing had to be redacted.
+    fn finish(self) -> Option<Vec<(Field, OwnedValue)>> {
+        self.redacted.then_some(self.values)
+    }
+}
+
+impl Visit for Collector<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push_text(field, value.to_string(), true);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push_text(field, format!("{value:?}"), false);
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.push_text(field, value.to_string(), false);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push_number(field, OwnedValue::I64(value), || value.to_string());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push_number(field, OwnedValue::U64(value), || value.to_string());
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.push_number(field, OwnedValue::I128(value), || value.to_string());
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.push_number(field, OwnedValue::U128(value), || value.to_string());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push_number(field, OwnedValue::F64(value), || value.to_string());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push_number(field, OwnedValue::Bool(value), || value.to_string());
+    }
+}
+
+fn with_value_set<R>(
+    fields: &FieldSet,
+    values: &[(Field, OwnedValue)],
+    f: impl FnOnce(&ValueSet<'_>) -> R,
+) -> R {
+    if values.len() > MAX_FIELDS {
+        // Logging from inside the layer would come back through it.
+        static DROPPED: Once = Once::new();
+        DROPPED.call_once(|| {
+            let dropped: Vec<_> = values[MAX_FIELDS..].iter().map(|(f, _)| f.name()).collect();
+            eprintln!(
+                "Redaction keeps the first {MAX_FIELDS} fields of a record; dropped {}",
+                dropped.join(", ")
+            );
+        });
+    }
+
+    // Unused slots hold no value, so the value set skips them.
+    let mut slots: [(&Field, Option<&dyn Value>); MAX_FIELDS] = [(&values[0].0, None); MAX_FIELDS];
+    for (slot, (field, value)) in slots.iter_mut().zip(values) {
+        *slot = (field, Some(value.as_value()));
+    }
+
+    f(&fields.value_set(&slots))
+}
+
+/// Passes spans and events on to `inner` with sensitive values replaced, so
+/// no log file or trace exporter behind it ever sees them.
+pub struct Redact<L> {
+    inner: L,
+    policy: RedactionPolicy,
+}
+
+impl<L> Redact<L> {
+    pub fn new(inner: L, policy: RedactionPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    fn redacted(
+        &self,
+        record: impl FnOnce(&mut Collector<'_>),
+    ) -> Option<Vec<(Field, OwnedValue)>> {
+        let mut collector = Collector::new(&self.policy);
+        record(&mut collector);
+        collector.finish()
+    }
+}
+
+impl<S, L> Layer<S> for Redact<L>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    L: Layer<S>,
+{
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.inner.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.enabled(metadata, ctx)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(values) = self.redacted(|collector| attrs.record(collector)) else {
+            return self.inner.on_new_span(attrs, id, ctx);
+        };
+
+        let metadata = attrs.metadata();
+        with_value_set(metadata.fields(), &values, |values| {
+            let attrs = if attrs.is_root() {
+                Attributes::new_root(metadata, values)
+            } else if let Some(parent) = attrs.parent() {
+                Attributes::child_of(parent.clone(), metadata, values)
+            } else {
+                Attributes::new(metadata, values)
+            };
+            self.inner.on_new_span(&attrs, id, ctx);
+        });
+    }
+
+    fn on_record(&self, span: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(metadata) = ctx.metadata(span) else {
+            return self.inner.on_record(span, values, ctx);
+        };
+        let Some(redacted) = self.redacted(|collector| values.record(collector)) else {
+            return self.inner.on_record(span, values, ctx);
+        };
+
+        with_value_set(metadata.fields(), &redacted, |values| {
+            self.inner.on_record(span, &Record::new(values), ctx);
+        });
+    }
+
+    fn on_follows_from(&self, span: &Id, follows: &Id, ctx: Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, ctx);
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.event_enabled(event, ctx)
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(values) = self.redacted(|collector| event.record(collector)) else {
+            return self.inner.on_event(event, ctx);
+        };
+
+        let metadata = event.metadata();
+        with_value_set(metadata.fields(), &values, |values| {
+            let event = if event.is_contextual() {
+                Event::new(metadata, values)
+            } else {
+                Event::new_child_of(event.parent().cloned(), metadata, values)
+            };
+            self.inner.on_event(&event, ctx);
+        });
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx);
+    }
+
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        self.inner.on_id_change(old, new, ctx);
+    }
+
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            Some(self as *const Self as *const ())
+        } else {
+            unsafe { self.inner.downcast_raw(id) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::{info, info_span};
+    use tracing_subscriber::{EnvFilter, layer::SubscriberExt, reload};
+
+    use super::*;
+    use crate::{
+        config::settings::LogFormat,
+        telemetry::{fmt_layer, redacting, tests::Buffer},
+    };
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    struct Credentials {
+        email: String,
+        password: String,
+    }
+
+    #[test]
+    fn logs_come_out_redacted() {
+        let buffer = Buffer::default();
+        let mut settings = RedactionSettings::default();
+        settings.fields.push("ip".to_string());
+
+        for format in [LogFormat::Json, LogFormat::Logfmt, LogFormat::Compact] {
+            let writer = buffer.clone();
+            let layer = fmt_layer(format, move || writer.clone(), false);
+            let (filter, _) = reload::Layer::new(EnvFilter::new("info"));
+            let subscriber = tracing_subscriber::registry()
+                .with(filter)
+                .with(redacting(vec![layer], &settings));
+
+            tracing::subscriber::with_default(subscriber, || {
+                let span = info_span!("login", user_email = "span@example.com");
+                let _entered = span.enter();
+
+                info!(
+                    email = "field@example.com",
+                    password = "field-password",
+                    access_token = "field-token",
+                    client_ip = %"192.0.2.1".parse::<IpAddr>().unwrap(),
+                    "Login"
+                );
+                info!(
+                    credentials = ?Credentials {
+                        email: "debug@example.com".to_string(),
+                        password: "debug-password".to_string(),
+                    },
+                    "Checking credentials"
+                );
+                info!("Login for text@example.com from 198.51.100.7: token=text-token");
+            });
+        }
+
+        let output = buffer.take();
+        for secret in [
+            "example.com",
+            "field-password",
+            "field-token",
+            "192.0.2.1",
+            "debug-password",
+            "198.51.100.7",
+            "text-token",
+        ] {
+            assert!(!output.contains(secret), "{secret} leaked into:\n{output}");
+        }
+        assert!(output.contains("Login for [REDACTED] from [REDACTED]: token=[REDACTED]"));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+
+/// Appends to `<directory>/<file_name>`, moving it aside to
+/// `<file_name>.<timestamp>` once it grows past `max_bytes` and deleting the
+/// oldest rotated files beyond `max_files`.
+pub struct SizeRollingAppender {
+    directory: PathBuf,
+    file_name: String,
+    max_bytes: u64,
+    max_files: Option<usize>,
+    file: File,
+    written: u64,
+    /// Timestamp of the last rotation, and how many rotations shared it.
+    last_rotation: Option<(String, u32)>,
+}
+
+impl SizeRollingAppender {
+    pub fn new(
+        directory: impl AsRef<Path>,
+        file_name: &str,
+        max_bytes: u64,
+        max_files: Option<usize>,
+    ) -> io::Result<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+
+        let file = open(&directory.join(file_name))?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            directory,
+            file_name: file_name.to_string(),
+            max_bytes,
+            max_files,
+            file,
+            written,
+            last_rotation: None,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let active = self.directory.join(&self.file_name);
+        let timestamp = Utc::now().format("%Y-%m-%d-%H-%M-%S%.3f").to_string();
+        // Rotations within one millisecond get a sequence number rather than
+        // overwriting each other; the names still sort chronologically.
+        let sequence = match &self.last_rotation {
+            Some((last, sequence)) if *last == timestamp => sequence + 1,
+            _ => 0,
+        };
+        let rotated = match sequence {
+            0 => format!("{}.{timestamp}", self.file_name),
+            n => format!("{}.{timestamp}.{n:03}", self.file_name),
+        };
+        fs::rename(&active, self.directory.join(rotated))?;
+        self.last_rotation = Some((timestamp, sequence));
+
+        self.file = open(&active)?;
+        self.written = 0;
+        self.prune()
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        let Some(max_files) = self.max_files else {
+            return Ok(());
+        };
+
+        let prefix = format!("{}.", self.file_name);
+        let mut rotated: Vec<PathBuf> = fs::read_dir(&self.directory)?
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with(&prefix))
+            })
+            .map(|entry| entry.path())
+            .collect();
+
+        // Timestamps sort chronologically; the active file counts towards the limit.
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(max_files.saturating_sub(1));
+        for path in &rotated[..excess] {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Write for SizeRollingAppender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("size-rolling-{}", uuid::Uuid::new_v4()))
+    }
+
+    /// Contents of the rotated files, oldest first, and of the active file.
+    fn contents(directory: &Path) -> (Vec<String>, String) {
+        let mut rotated: Vec<PathBuf> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.file_name().unwrap() != "app.log")
+            .collect();
+        rotated.sort();
+
+        let rotated = rotated
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        let active = fs::read_to_string(directory.join("app.log")).unwrap();
+        (rotated, active)
+    }
+
+    #[test]
+    fn rotates_once_a_write_would_pass_the_threshold() {
+        let directory = scratch_dir();
+        let mut appender = SizeRollingAppender::new(&directory, "app.log", 10, None).unwrap();
+
+        appender.write_all(b"12345").unwrap();
+        appender.write_all(b"67890").unwrap();
+        assert_eq!(contents(&directory), (vec![], "1234567890".to_string()));
+
+        appender.write_all(b"x").unwrap();
+        appender.flush().unwrap();
+        assert_eq!(
+            contents(&directory),
+            (vec!["1234567890".to_string()], "x".to_string())
+        );
+
+        // A single oversized write still lands whole in a fresh file.
+        appender.write_all(b"an oversized line").unwrap();
+        appender.flush().unwrap();
+        let (rotated, active) = contents(&directory);
+        assert_eq!(rotated.len(), 2);
+        assert_eq!(active, "an oversized line");
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn keeps_only_the_newest_files() {
+        let directory = scratch_dir();
+        let mut appender = SizeRollingAppender::new(&directory, "app.log", 1, Some(3)).unwrap();
+
+        for line in ["a", "b", "c", "d", "e"] {
+            appender.write_all(line.as_bytes()).unwrap();
+        }
+        appender.flush().unwrap();
+
+        // The active file counts towards the three retained.
+        assert_eq!(
+            contents(&directory),
+            (vec!["c".to_string(), "d".to_string()], "e".to_string())
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}